@@ -0,0 +1,128 @@
+use std::{fmt, fs, path::Path, str::FromStr};
+
+use clap::Parser;
+
+use crate::CmdExector;
+
+use super::verify_input;
+
+#[derive(Debug, Parser)]
+pub struct CodegenOpts {
+    #[arg(short, long, value_parser = verify_input, default_value = "-")]
+    pub input: String,
+
+    #[arg(
+        long,
+        value_parser = parse_sample_format,
+        help = "Sample format, inferred from the input extension when omitted"
+    )]
+    pub input_format: Option<SampleFormat>,
+
+    #[arg(short, long, value_parser = parse_codegen_lang, default_value = "rust")]
+    pub lang: CodegenLang,
+
+    #[arg(short, long, default_value = "Record")]
+    pub name: String,
+
+    #[arg(short, long)]
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SampleFormat {
+    Csv,
+    Json,
+    Yaml,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum CodegenLang {
+    Rust,
+    TypeScript,
+    JsonSchema,
+}
+
+fn parse_sample_format(format: &str) -> Result<SampleFormat, anyhow::Error> {
+    format.parse()
+}
+
+fn parse_codegen_lang(lang: &str) -> Result<CodegenLang, anyhow::Error> {
+    lang.parse()
+}
+
+impl FromStr for SampleFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(SampleFormat::Csv),
+            "json" => Ok(SampleFormat::Json),
+            "yaml" | "yml" => Ok(SampleFormat::Yaml),
+            _ => Err(anyhow::anyhow!("Invalid format")),
+        }
+    }
+}
+
+impl From<SampleFormat> for &'static str {
+    fn from(s: SampleFormat) -> Self {
+        match s {
+            SampleFormat::Csv => "csv",
+            SampleFormat::Json => "json",
+            SampleFormat::Yaml => "yaml",
+        }
+    }
+}
+
+impl fmt::Display for SampleFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl FromStr for CodegenLang {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rust" => Ok(CodegenLang::Rust),
+            "typescript" | "ts" => Ok(CodegenLang::TypeScript),
+            "json-schema" => Ok(CodegenLang::JsonSchema),
+            _ => Err(anyhow::anyhow!("Invalid language")),
+        }
+    }
+}
+
+impl From<CodegenLang> for &'static str {
+    fn from(s: CodegenLang) -> Self {
+        match s {
+            CodegenLang::Rust => "rust",
+            CodegenLang::TypeScript => "typescript",
+            CodegenLang::JsonSchema => "json-schema",
+        }
+    }
+}
+
+impl fmt::Display for CodegenLang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl CmdExector for CodegenOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let format = match self.input_format {
+            Some(format) => format,
+            None => Path::new(&self.input)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .ok_or_else(|| anyhow::anyhow!("Cannot infer sample format, use --input-format"))?
+                .parse()?,
+        };
+        let code = crate::process_codegen(&self.input, format, self.lang, &self.name)?;
+        match self.output {
+            Some(output) => fs::write(output, code)?,
+            None => print!("{}", code),
+        }
+        Ok(())
+    }
+}
@@ -1,11 +1,47 @@
 use anyhow::Ok;
 use clap::Parser;
 use core::fmt;
-use std::str::FromStr;
+use enum_dispatch::enum_dispatch;
+use std::{path::PathBuf, str::FromStr};
 
 use crate::CmdExector;
 
-use super::verify_input;
+use super::{verify_input, verify_path};
+
+/// `acli csv -i file.csv -f json` still converts without naming `convert`.
+#[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct CsvCommand {
+    #[command(subcommand)]
+    pub cmd: Option<CsvSubCommand>,
+
+    #[command(flatten)]
+    pub convert: Option<CsvOpts>,
+}
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum CsvSubCommand {
+    #[command(about = "Show CSV, or convert CSV to other formats")]
+    Convert(CsvOpts),
+    #[command(about = "Show keyed differences between two CSV files")]
+    Diff(CsvDiffOpts),
+    #[command(about = "Validate a CSV file against a schema")]
+    Validate(CsvValidateOpts),
+    #[command(about = "Mask or pseudonymize sensitive columns")]
+    Mask(CsvMaskOpts),
+    #[command(about = "Split a CSV file by row count, size or column value")]
+    Split(CsvSplitOpts),
+    #[command(about = "Transform each record with a rhai script")]
+    Map(CsvMapOpts),
+    #[command(name = "to-sqlite", about = "Import a CSV file into a SQLite table")]
+    ToSqlite(CsvToSqliteOpts),
+    #[command(
+        name = "from-sqlite",
+        about = "Export a SQLite table or query to other formats"
+    )]
+    FromSqlite(CsvFromSqliteOpts),
+}
 
 #[derive(Debug, Parser)]
 pub struct CsvOpts {
@@ -23,23 +59,237 @@ pub struct CsvOpts {
 
     #[arg(long, default_value_t = true)]
     pub header: bool,
+
+    #[arg(long, value_parser = verify_input, help = "rhai script applied to each record")]
+    pub script: Option<String>,
+
+    #[arg(long, help = "Worksheet to read when the input is an xlsx/ods file")]
+    pub sheet: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvMapOpts {
+    #[arg(short, long, value_parser = verify_input, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[arg(
+        short,
+        long,
+        value_parser = verify_input,
+        help = "rhai script defining `fn transform(record)`"
+    )]
+    pub script: String,
+
+    #[arg(short, long, default_value_t = ',')]
+    pub delimiter: char,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvDiffOpts {
+    #[arg(value_parser = verify_input)]
+    pub old: String,
+
+    #[arg(value_parser = verify_input)]
+    pub new: String,
+
+    #[arg(short, long, help = "Column used to match rows between the two files")]
+    pub key: String,
+
+    #[arg(short, long, default_value_t = ',')]
+    pub delimiter: char,
+
+    #[arg(long, value_parser = parse_diff_format, default_value = "table")]
+    pub format: CsvDiffFormat,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvValidateOpts {
+    #[arg(short, long, value_parser = verify_input, default_value = "-")]
+    pub input: String,
+
+    #[arg(
+        short,
+        long,
+        value_parser = verify_input,
+        help = "Column schema (YAML) or a JSON Schema describing one row"
+    )]
+    pub schema: String,
+
+    #[arg(short, long, default_value_t = ',')]
+    pub delimiter: char,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvMaskOpts {
+    #[arg(short, long, value_parser = verify_input, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[arg(
+        short,
+        long = "column",
+        value_parser = parse_mask_rule,
+        required = true,
+        help = "<column>:<hash|redact|year-only|truncate:N|fake>, repeatable"
+    )]
+    pub columns: Vec<MaskRule>,
+
+    #[arg(short, long, value_parser = verify_input, help = "Blake3 key file used by hash and fake")]
+    pub key: Option<String>,
+
+    #[arg(short, long, default_value_t = ',')]
+    pub delimiter: char,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvSplitOpts {
+    #[arg(short, long, value_parser = verify_input, default_value = "-")]
+    pub input: String,
+
+    #[arg(long, conflicts_with_all = ["bytes", "by"], required_unless_present_any = ["bytes", "by"], help = "Rows per chunk")]
+    pub rows: Option<usize>,
+
+    #[arg(
+        long,
+        conflicts_with = "by",
+        help = "Maximum bytes per chunk, header included"
+    )]
+    pub bytes: Option<u64>,
+
+    #[arg(long, help = "Write one file per distinct value of this column")]
+    pub by: Option<String>,
+
+    #[arg(short, long, value_parser = verify_path, default_value = ".")]
+    pub output_dir: PathBuf,
+
+    #[arg(
+        short,
+        long,
+        help = "Output file name, supports {stem}, {index} and {value} [default: {stem}-{index}.csv, or {value}.csv with --by]"
+    )]
+    pub template: Option<String>,
+
+    #[arg(short, long, default_value_t = ',')]
+    pub delimiter: char,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvToSqliteOpts {
+    #[arg(short, long, value_parser = verify_input, default_value = "-")]
+    pub input: String,
+
+    #[arg(long)]
+    pub db: String,
+
+    #[arg(short, long)]
+    pub table: String,
+
+    #[arg(
+        long,
+        conflicts_with = "replace",
+        help = "Insert into the table if it exists"
+    )]
+    pub append: bool,
+
+    #[arg(long, help = "Drop and recreate the table if it exists")]
+    pub replace: bool,
+
+    #[arg(long, default_value_t = 1000, help = "Rows per transaction")]
+    pub batch_size: usize,
+
+    #[arg(short, long, default_value_t = ',')]
+    pub delimiter: char,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvFromSqliteOpts {
+    #[arg(long, value_parser = verify_input)]
+    pub db: String,
+
+    #[arg(
+        short,
+        long,
+        conflicts_with = "query",
+        required_unless_present = "query"
+    )]
+    pub table: Option<String>,
+
+    #[arg(short, long)]
+    pub query: Option<String>,
+
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    #[arg(short, long, value_parser = parse_format)]
+    pub format: OutputFormat,
+}
+
+/// What `csv to-sqlite` does when the table already exists.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SqliteMode {
+    Create,
+    Append,
+    Replace,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SplitMode {
+    Rows(usize),
+    Bytes(u64),
+    Column(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct MaskRule {
+    pub column: String,
+    pub method: MaskMethod,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaskMethod {
+    Hash,
+    Redact,
+    YearOnly,
+    Truncate(usize),
+    Fake,
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum OutputFormat {
     Json,
     Yaml,
+    Xlsx,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum CsvDiffFormat {
+    Table,
+    Json,
 }
 
 fn parse_format(format: &str) -> Result<OutputFormat, anyhow::Error> {
     format.parse::<OutputFormat>()
 }
 
+fn parse_diff_format(format: &str) -> Result<CsvDiffFormat, anyhow::Error> {
+    format.parse::<CsvDiffFormat>()
+}
+
+fn parse_mask_rule(rule: &str) -> Result<MaskRule, anyhow::Error> {
+    rule.parse()
+}
+
 impl From<OutputFormat> for &'static str {
     fn from(s: OutputFormat) -> Self {
         match s {
             OutputFormat::Json => "json",
             OutputFormat::Yaml => "yaml",
+            OutputFormat::Xlsx => "xlsx",
         }
     }
 }
@@ -51,6 +301,7 @@ impl FromStr for OutputFormat {
         match s {
             "json" => Ok(OutputFormat::Json),
             "yaml" => Ok(OutputFormat::Yaml),
+            "xlsx" => Ok(OutputFormat::Xlsx),
             _ => Err(anyhow::anyhow!("Invalid format")),
         }
     }
@@ -67,6 +318,76 @@ impl fmt::Display for OutputFormat {
     }
 }
 
+impl From<CsvDiffFormat> for &'static str {
+    fn from(s: CsvDiffFormat) -> Self {
+        match s {
+            CsvDiffFormat::Table => "table",
+            CsvDiffFormat::Json => "json",
+        }
+    }
+}
+
+impl FromStr for CsvDiffFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(CsvDiffFormat::Table),
+            "json" => Ok(CsvDiffFormat::Json),
+            _ => Err(anyhow::anyhow!("Invalid format")),
+        }
+    }
+}
+
+impl fmt::Display for CsvDiffFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl FromStr for MaskMethod {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hash" => Ok(MaskMethod::Hash),
+            "redact" => Ok(MaskMethod::Redact),
+            "year-only" => Ok(MaskMethod::YearOnly),
+            "fake" => Ok(MaskMethod::Fake),
+            _ => match s.strip_prefix("truncate:") {
+                Some(n) => Ok(MaskMethod::Truncate(n.parse()?)),
+                None => Err(anyhow::anyhow!("Invalid mask method: {}", s)),
+            },
+        }
+    }
+}
+
+impl FromStr for MaskRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (column, method) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("Expected <column>:<method>, got {}", s))?;
+        Ok(MaskRule {
+            column: column.to_string(),
+            method: method.parse()?,
+        })
+    }
+}
+
+impl CmdExector for CsvCommand {
+    async fn execute(self) -> anyhow::Result<()> {
+        match (self.cmd, self.convert) {
+            (Some(cmd), _) => cmd.execute().await,
+            (None, Some(convert)) => convert.execute().await,
+            (None, None) => Err(anyhow::anyhow!(
+                "Expected a subcommand, or the convert options such as -i and -f"
+            )),
+        }
+    }
+}
+
 impl CmdExector for CsvOpts {
     async fn execute(self) -> anyhow::Result<()> {
         let outout = if let Some(output) = &self.output {
@@ -74,7 +395,135 @@ impl CmdExector for CsvOpts {
         } else {
             format!("output.{}", self.format)
         };
-        crate::process_csv(&self.input, outout, self.format)?;
+        crate::process_csv(
+            &self.input,
+            outout,
+            self.format,
+            self.script.as_deref(),
+            self.sheet.as_deref(),
+        )?;
+        Ok(())
+    }
+}
+
+impl CmdExector for CsvDiffOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let diff = crate::process_csv_diff(&self.old, &self.new, &self.key, self.delimiter as u8)?;
+        match self.format {
+            CsvDiffFormat::Table => println!("{}", diff.to_table()),
+            CsvDiffFormat::Json => println!("{}", serde_json::to_string_pretty(&diff)?),
+        }
+        // non-zero exit code lets CI jobs fail on unexpected changes
+        if !diff.is_empty() {
+            std::process::exit(1);
+        }
+        Ok(())
+    }
+}
+
+impl CmdExector for CsvValidateOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let count =
+            crate::process_csv_validate(&self.input, &self.schema, self.delimiter as u8, |v| {
+                println!("{}", v)
+            })?;
+        if count > 0 {
+            eprintln!("❌ {} violations found", count);
+            std::process::exit(1);
+        }
+        eprintln!("✅ No violations found");
+        Ok(())
+    }
+}
+
+impl CmdExector for CsvMaskOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let key = self.key.as_deref().map(crate::get_content).transpose()?;
+        crate::process_csv_mask(
+            &self.input,
+            &self.output,
+            &self.columns,
+            key.as_deref(),
+            self.delimiter as u8,
+        )?;
+        Ok(())
+    }
+}
+
+impl CmdExector for CsvSplitOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let mode = match (self.rows, self.bytes, self.by) {
+            (Some(rows), _, _) => SplitMode::Rows(rows),
+            (_, Some(bytes), _) => SplitMode::Bytes(bytes),
+            (_, _, Some(column)) => SplitMode::Column(column),
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "One of --rows, --bytes or --by is required"
+                ))
+            }
+        };
+        let template = self.template.unwrap_or_else(|| match mode {
+            SplitMode::Column(_) => "{value}.csv".to_string(),
+            _ => "{stem}-{index}.csv".to_string(),
+        });
+        let files = crate::process_csv_split(
+            &self.input,
+            &mode,
+            &self.output_dir,
+            &template,
+            self.delimiter as u8,
+        )?;
+        for file in files {
+            println!("{}", file.display());
+        }
+        Ok(())
+    }
+}
+
+impl CmdExector for CsvMapOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        crate::process_csv_map(
+            &self.input,
+            &self.output,
+            &self.script,
+            self.delimiter as u8,
+        )?;
+        Ok(())
+    }
+}
+
+impl CmdExector for CsvToSqliteOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let mode = match (self.append, self.replace) {
+            (true, _) => SqliteMode::Append,
+            (_, true) => SqliteMode::Replace,
+            _ => SqliteMode::Create,
+        };
+        let count = crate::process_csv_to_sqlite(
+            &self.input,
+            &self.db,
+            &self.table,
+            mode,
+            self.batch_size,
+            self.delimiter as u8,
+        )?;
+        eprintln!("Imported {} rows into {}", count, self.table);
+        Ok(())
+    }
+}
+
+impl CmdExector for CsvFromSqliteOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let query = match (&self.query, &self.table) {
+            (Some(query), _) => query.clone(),
+            (_, Some(table)) => crate::sqlite_table_query(table),
+            _ => return Err(anyhow::anyhow!("One of --table or --query is required")),
+        };
+        let output = self
+            .output
+            .unwrap_or_else(|| format!("output.{}", self.format));
+        let count = crate::process_sqlite_export(&self.db, &query, &output, self.format)?;
+        eprintln!("Exported {} rows to {}", count, output);
         Ok(())
     }
 }
@@ -1,37 +1,588 @@
-use crate::CmdExector;
-use clap::Parser;
+use std::{fmt, io::Write, str::FromStr, sync::Arc};
+
+use crate::{CmdExector, GenPassConfig, Pattern};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use clap::{ArgAction, Args, Parser};
+use enum_dispatch::enum_dispatch;
 use zxcvbn::zxcvbn;
 
+use super::verify_input;
+
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct GenPassOpts {
-    #[arg(short, long, default_value_t = 16)]
-    pub length: u8,
+    #[command(subcommand)]
+    pub cmd: Option<GenPassSubCommand>,
+
+    #[command(flatten)]
+    pub rules: PasswordRulesOpts,
+
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u8).range(0..=4),
+        conflicts_with = "words",
+        help = "Regenerate until the zxcvbn score reaches this value"
+    )]
+    pub min_score: Option<u8>,
+
+    #[arg(
+        short,
+        long,
+        conflicts_with_all = ["policy", "charset", "words", "min_score"],
+        help = "Template like XXXX-9999-xxxx (X upper, x lower, 9 digit, A letter, * alphanumeric, ! symbol) or a /regex/"
+    )]
+    pub pattern: Option<String>,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["charset", "pattern", "words"],
+        help = "Build the password from syllables that are easy to read out, with less entropy"
+    )]
+    pub pronounceable: bool,
+
+    #[arg(
+        short,
+        long,
+        conflicts_with_all = ["policy", "charset"],
+        num_args = 0..=1,
+        default_missing_value = "6",
+        help = "Generate a passphrase of N words instead [default: 6]"
+    )]
+    pub words: Option<usize>,
+
+    #[arg(
+        long,
+        value_parser = verify_input,
+        help = "Wordlist for --words, one word per line or the EFF diceware format"
+    )]
+    pub wordlist: Option<String>,
+
+    #[arg(long, default_value = "-")]
+    pub separator: String,
+
+    #[arg(long, help = "Capitalize each word of the passphrase")]
+    pub capitalize: bool,
+
+    #[arg(long, help = "Append a digit to the passphrase")]
+    pub append_number: bool,
+
+    #[arg(long, help = "Append a symbol to the passphrase")]
+    pub append_symbol: bool,
+
+    #[arg(
+        short,
+        long,
+        help = "Number of secrets to generate [default: number of names, or 1]"
+    )]
+    pub count: Option<usize>,
+
+    #[arg(
+        short,
+        long = "name",
+        value_delimiter = ',',
+        help = "Secret names, e.g. DB_PASSWORD,API_KEY; {index} is replaced by the 1-based index"
+    )]
+    pub names: Vec<String>,
+
+    #[arg(long, value_parser = parse_secret_format, default_value = "plain")]
+    pub format: SecretFormat,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
 
-    #[arg(long, default_value_t = true)]
+    #[arg(
+        long,
+        value_parser = verify_input,
+        help = "Encrypt the output with this key, decrypt with `text decrypt`"
+    )]
+    pub encrypt_key: Option<String>,
+
+    #[arg(
+        long,
+        value_parser = verify_input,
+        conflicts_with_all = ["pattern", "words"],
+        help = "Regenerate passwords found in this Have I Been Pwned dataset, see `genpass breached`"
+    )]
+    pub breach_db: Option<String>,
+
+    #[arg(long, value_parser = parse_breach_hash, default_value = "sha1")]
+    pub breach_hash: BreachHash,
+}
+
+/// Character rules shared by generated and derived passwords.
+#[derive(Debug, Args)]
+pub struct PasswordRulesOpts {
+    #[arg(short, long, help = "Password length [default: 16]")]
+    pub length: Option<usize>,
+
+    #[arg(
+        long,
+        conflicts_with = "charset",
+        help = "Policy YAML file, or one of the built-in policies: alnum, legacy, pin, strict"
+    )]
+    pub policy: Option<String>,
+
+    #[arg(long = "no-uppercase", action = ArgAction::SetFalse, help = "Exclude uppercase letters")]
     pub uppercase: bool,
 
-    #[arg(long, default_value_t = true)]
+    #[arg(long = "no-lowercase", action = ArgAction::SetFalse, help = "Exclude lowercase letters")]
     pub lowercase: bool,
 
-    #[arg(long, default_value_t = true)]
+    #[arg(long = "no-number", action = ArgAction::SetFalse, help = "Exclude digits")]
     pub number: bool,
 
-    #[arg(long, default_value_t = true)]
+    #[arg(long = "no-symbol", action = ArgAction::SetFalse, help = "Exclude symbols")]
     pub symbol: bool,
+
+    #[arg(long, help = "Include the easily confused characters 0, O, l and I")]
+    pub ambiguous: bool,
+
+    #[arg(long, conflicts_with_all = ["ambiguous"], help = "Custom alphabet, replaces the character classes")]
+    pub charset: Option<String>,
+
+    #[arg(
+        long,
+        default_value = "",
+        hide_default_value = true,
+        help = "Characters to leave out"
+    )]
+    pub exclude: String,
 }
 
-impl CmdExector for GenPassOpts {
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum GenPassSubCommand {
+    #[command(about = "Estimate the strength of passwords, one per line")]
+    Check(PassCheckOpts),
+    #[command(about = "Derive a stable per-site password from a master password")]
+    Derive(PassDeriveOpts),
+    #[command(about = "Look up passwords in a local Have I Been Pwned dataset")]
+    Breached(PassBreachedOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct PassCheckOpts {
+    #[arg(short, long, value_parser = verify_input, default_value = "-")]
+    pub input: String,
+
+    #[arg(
+        short,
+        long = "user-input",
+        help = "Word an attacker would try for this user, e.g. username or company, repeatable"
+    )]
+    pub user_inputs: Vec<String>,
+
+    #[arg(long, value_parser = parse_check_format, default_value = "text")]
+    pub format: CheckFormat,
+}
+
+#[derive(Debug, Parser)]
+pub struct PassDeriveOpts {
+    #[arg(short, long, help = "Site or service name, case-insensitive")]
+    pub site: String,
+
+    #[arg(long, default_value = "", help = "Login or username on the site")]
+    pub login: String,
+
+    #[arg(long, default_value_t = 1, help = "Bump to rotate the password")]
+    pub counter: u32,
+
+    #[arg(
+        long,
+        value_parser = verify_input,
+        help = "Read the master password from the first line of a file instead of prompting"
+    )]
+    pub master_file: Option<String>,
+
+    #[command(flatten)]
+    pub rules: PasswordRulesOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct PassBreachedOpts {
+    #[arg(short, long, value_parser = verify_input, default_value = "-")]
+    pub input: String,
+
+    #[arg(
+        long,
+        value_parser = verify_input,
+        help = "Sorted HASH:COUNT file, or a directory of 5 character prefix range files"
+    )]
+    pub db: String,
+
+    #[arg(long, value_parser = parse_breach_hash, default_value = "sha1")]
+    pub hash: BreachHash,
+
+    #[arg(long, value_parser = parse_check_format, default_value = "text")]
+    pub format: CheckFormat,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum BreachHash {
+    Sha1,
+    Ntlm,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum CheckFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SecretFormat {
+    Plain,
+    Json,
+    Yaml,
+    Env,
+}
+
+fn parse_secret_format(format: &str) -> Result<SecretFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl From<SecretFormat> for &'static str {
+    fn from(f: SecretFormat) -> Self {
+        match f {
+            SecretFormat::Plain => "plain",
+            SecretFormat::Json => "json",
+            SecretFormat::Yaml => "yaml",
+            SecretFormat::Env => "env",
+        }
+    }
+}
+
+impl FromStr for SecretFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(SecretFormat::Plain),
+            "json" => Ok(SecretFormat::Json),
+            "yaml" => Ok(SecretFormat::Yaml),
+            "env" => Ok(SecretFormat::Env),
+            _ => Err(anyhow::anyhow!("Invalid format")),
+        }
+    }
+}
+
+impl fmt::Display for SecretFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+fn parse_breach_hash(hash: &str) -> Result<BreachHash, anyhow::Error> {
+    hash.parse()
+}
+
+impl From<BreachHash> for &'static str {
+    fn from(h: BreachHash) -> Self {
+        match h {
+            BreachHash::Sha1 => "sha1",
+            BreachHash::Ntlm => "ntlm",
+        }
+    }
+}
+
+impl FromStr for BreachHash {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha1" => Ok(BreachHash::Sha1),
+            "ntlm" => Ok(BreachHash::Ntlm),
+            _ => Err(anyhow::anyhow!("Invalid hash")),
+        }
+    }
+}
+
+impl fmt::Display for BreachHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+fn parse_check_format(format: &str) -> Result<CheckFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl From<CheckFormat> for &'static str {
+    fn from(f: CheckFormat) -> Self {
+        match f {
+            CheckFormat::Text => "text",
+            CheckFormat::Json => "json",
+        }
+    }
+}
+
+impl FromStr for CheckFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(CheckFormat::Text),
+            "json" => Ok(CheckFormat::Json),
+            _ => Err(anyhow::anyhow!("Invalid format")),
+        }
+    }
+}
+
+impl fmt::Display for CheckFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl CmdExector for PassCheckOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let ret = crate::process_genpass(
-            self.length,
-            self.uppercase,
-            self.lowercase,
-            self.number,
-            self.symbol,
+        let reports = crate::process_pass_check(&self.input, &self.user_inputs)?;
+        match self.format {
+            CheckFormat::Text => {
+                for report in reports {
+                    println!("{}", report);
+                }
+            }
+            CheckFormat::Json => println!("{}", serde_json::to_string_pretty(&reports)?),
+        }
+        Ok(())
+    }
+}
+
+impl PasswordRulesOpts {
+    fn config(&self) -> anyhow::Result<GenPassConfig> {
+        let mut config = match &self.policy {
+            Some(policy) => GenPassConfig::from_policy(policy)?,
+            None => GenPassConfig::default(),
+        };
+        // flags can only tighten a policy
+        if let Some(length) = self.length {
+            let min = config.min_length.unwrap_or(config.length);
+            if self.policy.is_some() && length < min {
+                return Err(anyhow::anyhow!(
+                    "Length {} is below the policy minimum of {}",
+                    length,
+                    min
+                ));
+            }
+            config.length = length;
+        }
+        // a class the policy requires must not be dropped by --no-*
+        // (--charset conflicts with --policy)
+        let required = [
+            ("uppercase", config.upper, self.uppercase, config.min_upper),
+            ("lowercase", config.lower, self.lowercase, config.min_lower),
+            ("number", config.number, self.number, config.min_number),
+            ("symbol", config.symbol, self.symbol, config.min_symbol),
+        ];
+        for (name, policy, enabled, min) in required {
+            if self.policy.is_some() && policy && min > 0 && !enabled {
+                return Err(anyhow::anyhow!(
+                    "The policy requires at least {} {} characters",
+                    min,
+                    name
+                ));
+            }
+        }
+        config.upper &= self.uppercase;
+        config.lower &= self.lowercase;
+        config.number &= self.number;
+        config.symbol &= self.symbol;
+        config.ambiguous |= self.ambiguous;
+        config.charset = self.charset.clone().or(config.charset);
+        config.exclude.push_str(&self.exclude);
+        Ok(config)
+    }
+}
+
+impl CmdExector for PassDeriveOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let config = self.rules.config()?;
+        let master = match &self.master_file {
+            Some(path) => std::fs::read_to_string(path)?
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            None => rpassword::prompt_password("Master password: ")?,
+        };
+        let ret = crate::process_genpass_derive(
+            &config,
+            master.as_bytes(),
+            &self.site,
+            &self.login,
+            self.counter,
         )?;
         println!("{}", ret);
-        let estimate = zxcvbn(&ret, &[])?;
-        eprint!("Password strength: {}", estimate.score());
+        Ok(())
+    }
+}
+
+impl GenPassOpts {
+    fn config(&self) -> anyhow::Result<GenPassConfig> {
+        let mut config = self.rules.config()?;
+        if let Some(min_score) = self.min_score {
+            // like the other rules, --min-score can only tighten a policy
+            if self.rules.policy.is_some() && min_score < config.min_score {
+                return Err(anyhow::anyhow!(
+                    "--min-score {} is below the policy minimum of {}",
+                    min_score,
+                    config.min_score
+                ));
+            }
+            config.min_score = min_score;
+        }
+        config.breach_db = self
+            .breach_db
+            .as_ref()
+            .map(|db| crate::BreachDb::open(db, self.breach_hash).map(Arc::new))
+            .transpose()?;
+        Ok(config)
+    }
+
+    /// Generate one password or passphrase with its entropy in bits.
+    fn generate(
+        &self,
+        config: &GenPassConfig,
+        pattern: Option<&Pattern>,
+        wordlist: Option<&str>,
+    ) -> anyhow::Result<(String, f64)> {
+        if let Some(pattern) = pattern {
+            return Ok((pattern.generate(), pattern.entropy()));
+        }
+        if self.pronounceable {
+            return crate::process_pronounceable(config);
+        }
+        match self.words {
+            Some(words) => crate::process_passphrase(
+                words,
+                wordlist,
+                &self.separator,
+                self.capitalize,
+                self.append_number,
+                self.append_symbol,
+            ),
+            None => Ok((crate::process_genpass(config)?, config.entropy()?)),
+        }
+    }
+}
+
+impl CmdExector for PassBreachedOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let db = crate::BreachDb::open(&self.db, self.hash)?;
+        let reports = crate::process_breached(&self.input, &db)?;
+        match self.format {
+            CheckFormat::Text => {
+                for report in &reports {
+                    match report.count {
+                        0 => println!("line {}: not found", report.line),
+                        n => println!("line {}: seen {} times in breaches", report.line, n),
+                    }
+                }
+            }
+            CheckFormat::Json => println!("{}", serde_json::to_string_pretty(&reports)?),
+        }
+        // non-zero exit code lets scripts reject breached passwords
+        if reports.iter().any(|r| r.count > 0) {
+            std::process::exit(1);
+        }
+        Ok(())
+    }
+}
+
+impl CmdExector for GenPassOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        if let Some(cmd) = self.cmd {
+            return cmd.execute().await;
+        }
+        let wordlist = self
+            .wordlist
+            .as_deref()
+            .map(std::fs::read_to_string)
+            .transpose()?;
+        let config = self.config()?;
+        let pattern = self.pattern.as_deref().map(Pattern::parse).transpose()?;
+        let count = self.count.unwrap_or(self.names.len().max(1));
+        let structured = count > 1
+            || self.encrypt_key.is_some()
+            || self.output != "-"
+            || !matches!(self.format, SecretFormat::Plain);
+        if !structured {
+            let (ret, entropy) = self.generate(&config, pattern.as_ref(), wordlist.as_deref())?;
+            println!("{}", ret);
+            let estimate = zxcvbn(&ret, &[])?;
+            eprintln!("Password strength: {}", estimate.score());
+            eprintln!("Entropy: {:.1} bits", entropy);
+            return Ok(());
+        }
+
+        let templates = match (self.names.is_empty(), count) {
+            (false, _) => self.names.clone(),
+            (true, 1) => vec!["PASSWORD".to_string()],
+            (true, _) => vec!["PASSWORD_{index}".to_string()],
+        };
+        let secrets = crate::secret_names(&templates, count)?
+            .into_iter()
+            .map(|name| {
+                let (password, entropy) =
+                    self.generate(&config, pattern.as_ref(), wordlist.as_deref())?;
+                crate::Secret::try_new(name, password, entropy)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let rendered = crate::process_secrets_render(&secrets, self.format)?;
+        let mut writer = crate::get_writer(&self.output)?;
+        match &self.encrypt_key {
+            Some(key) => {
+                // the plaintext only ever lives in memory
+                let key = crate::get_content(key)?;
+                let encrypted = crate::process_text_encrypt(&mut rendered.as_bytes(), &key)?;
+                writeln!(writer, "{}", URL_SAFE_NO_PAD.encode(encrypted))?;
+            }
+            None => writer.write_all(rendered.as_bytes())?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(args: &[&str]) -> anyhow::Result<GenPassConfig> {
+        let opts = GenPassOpts::try_parse_from(["genpass"].iter().chain(args))?;
+        opts.config()
+    }
+
+    #[test]
+    fn test_rules_tighten_policy() -> anyhow::Result<()> {
+        assert_eq!(config(&["--policy", "strict", "-l", "32"])?.length, 32);
+        assert_eq!(config(&["--policy", "legacy", "-l", "8"])?.length, 8);
+        assert!(!config(&["--policy", "alnum", "--no-symbol"])?.symbol);
+        assert!(!config(&["--no-symbol", "-l", "4"])?.symbol);
+        assert_eq!(
+            config(&["--policy", "legacy", "--min-score", "3"])?.min_score,
+            3
+        );
+        assert!(config(&["--policy", "strict", "--min-score", "0"]).is_err());
+        assert_eq!(
+            config(&["--policy", "strict", "--min-score", "4"])?.min_score,
+            4
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rules_reject_short_length() {
+        assert!(config(&["--policy", "strict", "-l", "12"]).is_err());
+        // without min_length the policy length is the minimum
+        assert!(config(&["--policy", "alnum", "-l", "19"]).is_err());
+    }
+
+    #[test]
+    fn test_rules_reject_dropped_class() -> anyhow::Result<()> {
+        assert!(config(&["--policy", "alnum", "--no-number"]).is_err());
+        assert!(config(&["--policy", "strict", "--no-symbol"]).is_err());
+        assert!(config(&["--policy", "strict", "--charset", "abc123"]).is_err());
         Ok(())
     }
 }
@@ -0,0 +1,136 @@
+use std::{fmt, str::FromStr};
+
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+
+use crate::{process_id, process_id_timestamp, CmdExector};
+
+#[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct IdOpts {
+    #[command(subcommand)]
+    pub cmd: Option<IdSubCommand>,
+
+    #[arg(
+        short,
+        long,
+        value_parser = parse_id_kind,
+        default_value = "uuid4",
+        help = "uuid4, uuid7, ulid, snowflake, nanoid, hex, base64 or base58"
+    )]
+    pub kind: IdKind,
+
+    #[arg(short, long, default_value_t = 1)]
+    pub count: usize,
+
+    #[arg(
+        short,
+        long,
+        help = "Characters for nanoid [default: 21], random bytes for hex/base64/base58 [default: 16]"
+    )]
+    pub length: Option<usize>,
+
+    #[arg(long, help = "Nanoid alphabet [default: A-Za-z0-9_-]")]
+    pub alphabet: Option<String>,
+
+    #[arg(long, help = "Snowflake worker id, 0 to 1023 [default: 0]")]
+    pub worker: Option<u16>,
+}
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum IdSubCommand {
+    #[command(about = "Print the timestamp embedded in a UUIDv7, ULID or snowflake")]
+    Parse(IdParseOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct IdParseOpts {
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum IdKind {
+    Uuid4,
+    Uuid7,
+    Ulid,
+    Snowflake,
+    Nanoid,
+    Hex,
+    Base64,
+    Base58,
+}
+
+fn parse_id_kind(kind: &str) -> Result<IdKind, anyhow::Error> {
+    kind.parse()
+}
+
+impl From<IdKind> for &'static str {
+    fn from(kind: IdKind) -> Self {
+        match kind {
+            IdKind::Uuid4 => "uuid4",
+            IdKind::Uuid7 => "uuid7",
+            IdKind::Ulid => "ulid",
+            IdKind::Snowflake => "snowflake",
+            IdKind::Nanoid => "nanoid",
+            IdKind::Hex => "hex",
+            IdKind::Base64 => "base64",
+            IdKind::Base58 => "base58",
+        }
+    }
+}
+
+impl FromStr for IdKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uuid" | "uuid4" => Ok(IdKind::Uuid4),
+            "uuid7" => Ok(IdKind::Uuid7),
+            "ulid" => Ok(IdKind::Ulid),
+            "snowflake" => Ok(IdKind::Snowflake),
+            "nanoid" => Ok(IdKind::Nanoid),
+            "hex" => Ok(IdKind::Hex),
+            "base64" => Ok(IdKind::Base64),
+            "base58" => Ok(IdKind::Base58),
+            _ => Err(anyhow::anyhow!("Invalid kind")),
+        }
+    }
+}
+
+impl fmt::Display for IdKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl CmdExector for IdOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        if let Some(cmd) = self.cmd {
+            return cmd.execute().await;
+        }
+        let ids = process_id(
+            self.kind,
+            self.count,
+            self.length,
+            self.alphabet.as_deref(),
+            self.worker,
+        )?;
+        for id in ids {
+            println!("{}", id);
+        }
+        Ok(())
+    }
+}
+
+impl CmdExector for IdParseOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let (kind, time) = process_id_timestamp(&self.id)?;
+        println!(
+            "{}: {}",
+            kind,
+            time.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+        );
+        Ok(())
+    }
+}
@@ -1,8 +1,11 @@
 mod base64;
+mod codegen;
 mod csv;
 mod genpass;
 mod http;
+mod id;
 mod jwt;
+mod otp;
 mod text;
 
 use std::path::{Path, PathBuf};
@@ -10,7 +13,9 @@ use std::path::{Path, PathBuf};
 use clap::Parser;
 use enum_dispatch::enum_dispatch;
 
-pub use self::{base64::*, csv::*, genpass::*, http::*, jwt::*, text::*};
+pub use self::{
+    base64::*, codegen::*, csv::*, genpass::*, http::*, id::*, jwt::*, otp::*, text::*,
+};
 
 #[derive(Debug, Parser)]
 #[command(name = "rcli", version, author, about, long_about = None)]
@@ -19,13 +24,19 @@ pub struct Opts {
     pub cmd: SubCommand,
 }
 
+// parsed once per run, boxing the larger options isn't worth it
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Parser)]
 #[enum_dispatch(CmdExector)]
 pub enum SubCommand {
-    #[command(name = "csv", about = "Show CSV, or convert CSV to other formats")]
-    Csv(CsvOpts),
+    #[command(about = "Convert or diff CSV files")]
+    Csv(CsvCommand),
     #[command(name = "genpass", about = "Generate a random password")]
     GenPass(GenPassOpts),
+    #[command(subcommand, about = "Generate and verify TOTP/HOTP one-time passwords")]
+    Otp(OtpSubCommand),
+    #[command(about = "Generate UUIDs, ULIDs, nanoids or random tokens")]
+    Id(IdOpts),
     #[command(subcommand, about = "Base64 encode/decode")]
     Base64(Base64SubCommand),
     #[command(subcommand, about = "Text sign/verify")]
@@ -34,6 +45,8 @@ pub enum SubCommand {
     Http(HttpSubCommand),
     #[command(subcommand, about = "JWT sign/verify")]
     Jwt(JwtSubCommand),
+    #[command(about = "Generate Rust/TypeScript/JSON Schema types from a CSV/JSON/YAML sample")]
+    Codegen(CodegenOpts),
 }
 
 fn verify_input(filename: &str) -> Result<String, &'static str> {
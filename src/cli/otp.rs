@@ -0,0 +1,204 @@
+use std::{fmt, str::FromStr};
+
+use clap::{Args, Parser};
+use data_encoding::BASE32_NOPAD;
+use enum_dispatch::enum_dispatch;
+
+use crate::{CmdExector, Otp};
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum OtpSubCommand {
+    #[command(about = "Print the current TOTP/HOTP code")]
+    Code(OtpCodeOpts),
+    #[command(about = "Verify a TOTP/HOTP code")]
+    Verify(OtpVerifyOpts),
+    #[command(about = "Generate a new secret and its otpauth:// URI")]
+    New(OtpNewOpts),
+}
+
+/// Where the token comes from, plus overrides for its parameters.
+#[derive(Debug, Args)]
+pub struct OtpSourceOpts {
+    #[arg(short, long, required_unless_present = "uri", help = "Base32 secret")]
+    pub secret: Option<String>,
+
+    #[arg(short, long, conflicts_with = "secret", help = "otpauth:// URI")]
+    pub uri: Option<String>,
+
+    #[arg(long, help = "HOTP instead of TOTP, with --counter")]
+    pub hotp: bool,
+
+    #[arg(long, help = "HOTP counter [default: 0]")]
+    pub counter: Option<u64>,
+
+    #[arg(short, long, value_parser = parse_otp_algorithm, help = "sha1, sha256 or sha512 [default: sha1]")]
+    pub algorithm: Option<OtpAlgorithm>,
+
+    #[arg(short, long, help = "Code length [default: 6]")]
+    pub digits: Option<u32>,
+
+    #[arg(short, long, help = "TOTP period in seconds [default: 30]")]
+    pub period: Option<u64>,
+
+    #[arg(long, help = "Unix time to use instead of now")]
+    pub time: Option<u64>,
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpCodeOpts {
+    #[command(flatten)]
+    pub source: OtpSourceOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpVerifyOpts {
+    pub code: String,
+
+    #[command(flatten)]
+    pub source: OtpSourceOpts,
+
+    #[arg(
+        short,
+        long,
+        default_value_t = 1,
+        help = "Accepted steps of clock drift (TOTP) or look-ahead (HOTP)"
+    )]
+    pub window: u64,
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpNewOpts {
+    #[arg(long)]
+    pub issuer: Option<String>,
+
+    #[arg(long)]
+    pub account: Option<String>,
+
+    #[arg(long, help = "HOTP instead of TOTP")]
+    pub hotp: bool,
+
+    #[arg(short, long, value_parser = parse_otp_algorithm, default_value = "sha1")]
+    pub algorithm: OtpAlgorithm,
+
+    #[arg(short, long, default_value_t = 6)]
+    pub digits: u32,
+
+    #[arg(short, long, default_value_t = 30)]
+    pub period: u64,
+
+    #[arg(
+        long,
+        help = "Print the URI as a QR code to scan with an authenticator app"
+    )]
+    pub qr: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum OtpAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+fn parse_otp_algorithm(algorithm: &str) -> Result<OtpAlgorithm, anyhow::Error> {
+    algorithm.parse()
+}
+
+impl From<OtpAlgorithm> for &'static str {
+    fn from(a: OtpAlgorithm) -> Self {
+        match a {
+            OtpAlgorithm::Sha1 => "sha1",
+            OtpAlgorithm::Sha256 => "sha256",
+            OtpAlgorithm::Sha512 => "sha512",
+        }
+    }
+}
+
+impl FromStr for OtpAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha1" => Ok(OtpAlgorithm::Sha1),
+            "sha256" => Ok(OtpAlgorithm::Sha256),
+            "sha512" => Ok(OtpAlgorithm::Sha512),
+            _ => Err(anyhow::anyhow!("Invalid algorithm")),
+        }
+    }
+}
+
+impl fmt::Display for OtpAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl OtpSourceOpts {
+    fn otp(&self) -> anyhow::Result<Otp> {
+        let mut otp = match (&self.uri, &self.secret) {
+            (Some(uri), _) => Otp::from_uri(uri)?,
+            (None, Some(secret)) if self.hotp => Otp::hotp(crate::decode_secret(secret)?, 0),
+            (None, Some(secret)) => Otp::totp(crate::decode_secret(secret)?),
+            (None, None) => unreachable!("clap requires --secret or --uri"),
+        };
+        otp.algorithm = self.algorithm.unwrap_or(otp.algorithm);
+        otp.digits = self.digits.unwrap_or(otp.digits);
+        otp.counter = self.counter.unwrap_or(otp.counter);
+        if otp.period.is_some() {
+            otp.period = self.period.or(otp.period);
+        }
+        otp.validate()?;
+        Ok(otp)
+    }
+
+    fn time(&self) -> anyhow::Result<u64> {
+        self.time.map(Ok).unwrap_or_else(crate::now)
+    }
+}
+
+impl CmdExector for OtpCodeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let otp = self.source.otp()?;
+        let time = self.source.time()?;
+        println!("{}", otp.code_at(otp.counter_at(time))?);
+        if let Some(remaining) = otp.remaining(time) {
+            eprintln!("Valid for {}s", remaining);
+        }
+        Ok(())
+    }
+}
+
+impl CmdExector for OtpVerifyOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let otp = self.source.otp()?;
+        match otp.verify(&self.code, self.source.time()?, self.window)? {
+            Some(offset) => {
+                println!("✓ Code is valid (step offset {})", offset);
+                Ok(())
+            }
+            None => {
+                println!("⚠ Code is invalid");
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+impl CmdExector for OtpNewOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let mut otp = Otp::generate((!self.hotp).then_some(self.period));
+        otp.algorithm = self.algorithm;
+        otp.digits = self.digits;
+        otp.issuer = self.issuer;
+        otp.account = self.account;
+        otp.validate()?;
+        let uri = otp.to_uri();
+        println!("Secret: {}", BASE32_NOPAD.encode(&otp.secret));
+        println!("URI: {}", uri);
+        if self.qr {
+            println!("{}", crate::process_qr_render(&uri)?);
+        }
+        Ok(())
+    }
+}
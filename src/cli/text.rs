@@ -1,22 +1,30 @@
-use std::{fmt, fs, path::PathBuf, str::FromStr};
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
+use anyhow::anyhow;
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
-use clap::Parser;
+use clap::{Args, Parser};
 use enum_dispatch::enum_dispatch;
 
-use crate::CmdExector;
+use crate::{CmdExector, SignatureFile};
 
 use super::{verify_input, verify_path};
 
 #[derive(Debug, Parser)]
 #[enum_dispatch(CmdExector)]
 pub enum TextSubCommand {
-    #[command(about = "Sign a text with a private/shared key")]
+    #[command(about = "Sign a text with a private/shared key into a minisign .sig file")]
     Sign(TextSignOpts),
-    #[command(about = "Verify a signature")]
+    #[command(about = "Verify a minisign/signify signature file")]
     Verify(TextVerifyOpts),
     #[command(about = "Generate a key pair")]
     Generate(KeyGenerateOpts),
+    #[command(subcommand, about = "Manage key files")]
+    Key(TextKeySubCommand),
     #[command(about = "Encrypt a text")]
     Encrypt(EncryptOpts),
     #[command(about = "Decrypt a text")]
@@ -31,34 +39,84 @@ pub struct TextSignOpts {
     pub key: String,
     #[arg(long, value_parser = parse_text_sign_format, default_value = "blake3")]
     pub format: TextSignFormat,
+    #[arg(
+        short,
+        long,
+        help = "Signature file [default: <input>.sig, stdout when reading stdin]"
+    )]
+    pub output: Option<String>,
+    #[arg(
+        short,
+        long,
+        help = "Signed comment [default: timestamp and file name]"
+    )]
+    pub trusted_comment: Option<String>,
+    #[arg(short = 'c', long, help = "Unsigned comment on the first line")]
+    pub untrusted_comment: Option<String>,
+    #[command(flatten)]
+    pub passphrase: PassphraseOpts,
 }
 
 #[derive(Debug, Parser)]
 pub struct TextVerifyOpts {
     #[arg(short, long, value_parser = verify_input, default_value = "-")]
     pub input: String,
-    #[arg(short, long, value_parser = verify_input)]
+    #[arg(
+        short,
+        long,
+        value_parser = verify_input,
+        help = "Shared key, raw public key or minisign public key"
+    )]
     pub key: String,
-    #[arg(short, long)]
-    pub signature: String,
-    #[arg(long, value_parser = parse_text_sign_format, default_value = "blake3")]
-    pub format: TextSignFormat,
+    #[arg(
+        short,
+        long,
+        help = "Signature file [default: <input>.sig], or a bare base64 signature from older versions"
+    )]
+    pub signature: Option<String>,
 }
 
 #[derive(Debug, Parser)]
 pub struct EncryptOpts {
     #[arg(short, long, value_parser = verify_input, default_value = "-")]
     pub input: String,
-    #[arg(short, long, value_parser = verify_input)]
-    pub key: String,
+    #[arg(
+        short,
+        long,
+        value_parser = verify_input,
+        required_unless_present_any = ["password", "passphrase_env", "passphrase_fd", "recipient"],
+        conflicts_with_all = ["password", "passphrase_env", "passphrase_fd", "recipient"]
+    )]
+    pub key: Option<String>,
+    #[arg(
+        short,
+        long,
+        conflicts_with_all = ["password", "passphrase_env", "passphrase_fd"],
+        help = "Encrypt to an age recipient (age1...) or a file of them, can be repeated"
+    )]
+    pub recipient: Vec<String>,
+    #[arg(
+        long,
+        help = "Encrypt with a password (Argon2id) instead of a key file"
+    )]
+    pub password: bool,
+    #[command(flatten)]
+    pub passphrase: PassphraseOpts,
 }
 
 #[derive(Debug, Parser)]
 pub struct DecryptOpts {
     #[arg(short, long, value_parser = verify_input, default_value = "-")]
     pub input: String,
-    #[arg(short, long, value_parser = verify_input)]
-    pub key: String,
+    #[arg(
+        short,
+        long,
+        value_parser = verify_input,
+        help = "Key file, or an age identity file for age encrypted input"
+    )]
+    pub key: Option<String>,
+    #[command(flatten)]
+    pub passphrase: PassphraseOpts,
 }
 
 #[derive(Debug, Parser)]
@@ -67,12 +125,103 @@ pub struct KeyGenerateOpts {
     pub format: TextSignFormat,
     #[arg(short, long, value_parser = verify_path)]
     pub output_path: PathBuf,
+    #[arg(long, default_value = "raw", help = "raw, pem, der or openssh")]
+    pub key_format: KeyFormat,
+    #[arg(
+        long,
+        help = "Encrypt the secret key with a passphrase (pem, der or openssh)"
+    )]
+    pub protect: bool,
+    #[command(flatten)]
+    pub passphrase: PassphraseOpts,
+}
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum TextKeySubCommand {
+    #[command(about = "Convert an Ed25519 key between raw, PEM, DER, OpenSSH and minisign")]
+    Convert(KeyConvertOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct KeyConvertOpts {
+    #[arg(short, long, value_parser = verify_input, default_value = "-")]
+    pub input: String,
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    #[arg(long, help = "raw, pem, der, openssh or minisign (public keys only)")]
+    pub to: KeyFormat,
+    #[arg(long, help = "Write the public key of a secret key")]
+    pub public: bool,
+    #[arg(
+        long,
+        help = "Read a raw 32 byte input as a public key rather than a secret key"
+    )]
+    pub raw_public: bool,
+    #[arg(long, help = "Encrypt the converted secret key with a passphrase")]
+    pub protect: bool,
+    #[command(flatten)]
+    pub passphrase: PassphraseOpts,
+}
+
+/// Where to read the passphrase of an encrypted key from instead of prompting
+/// for it, so it can be supplied in CI.
+#[derive(Debug, Args)]
+pub struct PassphraseOpts {
+    #[arg(
+        long,
+        value_name = "VAR",
+        help = "Read the passphrase from an environment variable"
+    )]
+    pub passphrase_env: Option<String>,
+    #[arg(
+        long,
+        value_name = "FD",
+        conflicts_with = "passphrase_env",
+        help = "Read the passphrase from a file descriptor, e.g. 3 with `3<secret.txt`"
+    )]
+    pub passphrase_fd: Option<u32>,
+}
+
+impl PassphraseOpts {
+    /// The passphrase from the env var or the first line read from the fd,
+    /// otherwise prompted for, twice if `confirm` is set.
+    pub fn read(&self, prompt: &str, confirm: bool) -> anyhow::Result<String> {
+        let passphrase = if let Some(var) = &self.passphrase_env {
+            std::env::var(var).map_err(|_| anyhow!("Environment variable {} is not set", var))?
+        } else if let Some(fd) = self.passphrase_fd {
+            let content = fs::read_to_string(format!("/dev/fd/{}", fd))?;
+            content.lines().next().unwrap_or_default().to_string()
+        } else {
+            let passphrase = rpassword::prompt_password(prompt)?;
+            if confirm && rpassword::prompt_password("Confirm passphrase: ")? != passphrase {
+                return Err(anyhow!("Passphrases don't match"));
+            }
+            passphrase
+        };
+        if passphrase.is_empty() {
+            return Err(anyhow!("Passphrase must not be empty"));
+        }
+        Ok(passphrase)
+    }
+
+    /// Decrypt `key` if it is an encrypted secret key, returning the raw seed.
+    fn unlock(&self, key: Vec<u8>) -> anyhow::Result<(Vec<u8>, Option<String>)> {
+        if !crate::is_encrypted_key(&key) {
+            return Ok((key, None));
+        }
+        let passphrase = self.read("Key passphrase: ", false)?;
+        let key = crate::load_encrypted_signing_key(&key, &passphrase)?;
+        Ok((key.to_bytes().to_vec(), Some(passphrase)))
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
 pub enum TextSignFormat {
     Blake3,
     Ed25519,
+    /// age encryption keys, they can't sign
+    X25519,
 }
 
 fn parse_text_sign_format(format: &str) -> Result<TextSignFormat, String> {
@@ -86,6 +235,7 @@ impl FromStr for TextSignFormat {
         match s {
             "blake3" => Ok(Self::Blake3),
             "ed25519" => Ok(Self::Ed25519),
+            "x25519" => Ok(Self::X25519),
             _ => Err(format!("Unknown format: {}", s)),
         }
     }
@@ -96,6 +246,7 @@ impl From<TextSignFormat> for &'static str {
         match format {
             TextSignFormat::Blake3 => "blake3",
             TextSignFormat::Ed25519 => "ed25519",
+            TextSignFormat::X25519 => "x25519",
         }
     }
 }
@@ -106,25 +257,106 @@ impl fmt::Display for TextSignFormat {
     }
 }
 
+/// Encodings of Ed25519 key files. PEM and DER are PKCS#8 for secret keys
+/// and SPKI for public keys.
+#[derive(Debug, Copy, Clone)]
+pub enum KeyFormat {
+    Raw,
+    Pem,
+    Der,
+    Openssh,
+    Minisign,
+}
+
+impl FromStr for KeyFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(Self::Raw),
+            "pem" => Ok(Self::Pem),
+            "der" => Ok(Self::Der),
+            "openssh" => Ok(Self::Openssh),
+            "minisign" => Ok(Self::Minisign),
+            _ => Err(format!("Unknown key format: {}", s)),
+        }
+    }
+}
+
+impl From<KeyFormat> for &'static str {
+    fn from(format: KeyFormat) -> Self {
+        match format {
+            KeyFormat::Raw => "raw",
+            KeyFormat::Pem => "pem",
+            KeyFormat::Der => "der",
+            KeyFormat::Openssh => "openssh",
+            KeyFormat::Minisign => "minisign",
+        }
+    }
+}
+
+impl fmt::Display for KeyFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
 impl CmdExector for TextSignOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let mut reader = crate::get_reader(&self.input)?;
-        let key = crate::get_content(&self.key)?;
-        let sig = crate::process_text_sign(&mut reader, &key, self.format)?;
-        let encoded = URL_SAFE_NO_PAD.encode(sig);
-        println!("{}", encoded);
+        let (key, _) = self.passphrase.unlock(crate::get_content(&self.key)?)?;
+        let sig = crate::process_text_sign_detached(
+            &self.input,
+            &key,
+            self.format,
+            self.trusted_comment,
+            self.untrusted_comment,
+        )?;
+        let output = match self.output {
+            Some(output) => output,
+            None if self.input == "-" => "-".to_string(),
+            None => format!("{}.sig", self.input),
+        };
+        crate::get_writer(&output)?.write_all(sig.to_string().as_bytes())?;
+        if output != "-" {
+            eprintln!("Signature written to {}", output);
+        }
         Ok(())
     }
 }
 
 impl CmdExector for TextVerifyOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let mut reader = crate::get_reader(&self.input)?;
         let key = crate::get_content(&self.key)?;
-        let decoded = URL_SAFE_NO_PAD.decode(&self.signature)?;
-        let verified = crate::process_text_verify(&mut reader, &key, &decoded, self.format)?;
+        let explicit = self.signature.is_some();
+        let sig_path = match self.signature {
+            Some(sig) => sig,
+            None if self.input == "-" => {
+                return Err(anyhow::anyhow!(
+                    "--signature is required when reading stdin"
+                ))
+            }
+            None => format!("{}.sig", self.input),
+        };
+        let (verified, trusted) = if !explicit || Path::new(&sig_path).exists() {
+            let sig: SignatureFile = fs::read_to_string(&sig_path)?.parse()?;
+            let verified = crate::process_text_verify_detached(&self.input, &key, &sig)?;
+            (verified, sig.trusted.map(|(comment, _)| comment))
+        } else {
+            // not a file: a bare signature as printed before signature files
+            let sig = URL_SAFE_NO_PAD.decode(sig_path.trim()).map_err(|_| {
+                anyhow!(
+                    "{} is neither a signature file nor a base64 signature",
+                    sig_path
+                )
+            })?;
+            let verified = crate::process_text_verify_raw(&self.input, &key, &sig)?;
+            (verified, None)
+        };
         if verified {
             println!("✅ Signature verified");
+            if let Some(comment) = trusted {
+                println!("Trusted comment: {}", comment);
+            }
         } else {
             println!("❌ Signature not verified");
         }
@@ -134,19 +366,87 @@ impl CmdExector for TextVerifyOpts {
 
 impl CmdExector for KeyGenerateOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let key = crate::process_text_key_generate(self.format)?;
+        let passphrase = match self.protect {
+            true => Some(self.passphrase.read("New passphrase: ", true)?),
+            false => None,
+        };
+        let key =
+            crate::process_text_key_generate(self.format, self.key_format, passphrase.as_deref())?;
         for (k, v) in key {
-            let _ = fs::write(self.output_path.join(k), v);
+            let secret = !(k.ends_with(".pk") || k.ends_with(".pub"));
+            write_key_file(&self.output_path.join(k), &v, secret)?;
         }
         Ok(())
     }
 }
 
+/// Write a generated key file. Secret and shared keys are only readable by
+/// their owner on unix, also when an existing file is overwritten.
+fn write_key_file(path: &Path, content: &[u8], secret: bool) -> anyhow::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if secret {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+    #[cfg(unix)]
+    if secret {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(content)?;
+    Ok(())
+}
+
+impl CmdExector for KeyConvertOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let (key, passphrase) = self.passphrase.unlock(crate::get_content(&self.input)?)?;
+        // an unlocked key is protected again with the same passphrase
+        let passphrase = match (self.protect, passphrase) {
+            (false, _) => None,
+            (true, Some(passphrase)) => Some(passphrase),
+            (true, None) => Some(self.passphrase.read("New passphrase: ", true)?),
+        };
+        let converted = crate::process_key_convert(
+            &key,
+            self.to,
+            self.public,
+            self.raw_public,
+            passphrase.as_deref(),
+        )?;
+        crate::get_writer(&self.output)?.write_all(&converted)?;
+        Ok(())
+    }
+}
+
 impl CmdExector for EncryptOpts {
     async fn execute(self) -> anyhow::Result<()> {
         let mut reader = crate::get_reader(&self.input)?;
-        let key = crate::get_content(&self.key)?;
-        let encrypted = crate::process_text_encrypt(&mut reader, &key)?;
+        if !self.recipient.is_empty() {
+            let mut recipients = Vec::new();
+            for recipient in &self.recipient {
+                let recipient = match Path::new(recipient).is_file() {
+                    true => fs::read_to_string(recipient)?,
+                    false => recipient.clone(),
+                };
+                recipients.extend(crate::parse_recipients(&recipient)?);
+            }
+            let encrypted = crate::process_text_encrypt_recipients(&mut reader, &recipients)?;
+            io::stdout().write_all(&encrypted)?;
+            return Ok(());
+        }
+        // --passphrase-env and --passphrase-fd imply --password
+        let encrypted = match &self.key {
+            Some(key) => crate::process_text_encrypt(&mut reader, &crate::get_content(key)?)?,
+            None => {
+                let password = self.passphrase.read("Password: ", true)?;
+                crate::process_text_encrypt_password(&mut reader, &password)?
+            }
+        };
         let encoded = URL_SAFE_NO_PAD.encode(encrypted);
         println!("{}", encoded);
         Ok(())
@@ -156,10 +456,55 @@ impl CmdExector for EncryptOpts {
 impl CmdExector for DecryptOpts {
     async fn execute(self) -> anyhow::Result<()> {
         let input: Vec<u8> = crate::get_content(&self.input)?;
-        let encrypted = URL_SAFE_NO_PAD.decode(input)?;
-        let key: Vec<u8> = crate::get_content(&self.key)?;
-        let decrypted = crate::process_text_decrypt(&mut encrypted.as_slice(), &key)?;
+        if crate::is_age_encrypted(&input) {
+            let identity = self
+                .key
+                .ok_or_else(|| anyhow!("--key with an age identity file is required"))?;
+            let decrypted = crate::process_text_decrypt_identity(
+                &mut input.as_slice(),
+                &crate::get_content(&identity)?,
+            )?;
+            // age files may hold binary data, written as is like `age -d`
+            io::stdout().write_all(&decrypted)?;
+            return Ok(());
+        }
+        let encrypted = URL_SAFE_NO_PAD.decode(input.trim_ascii())?;
+        let decrypted = if crate::is_password_encrypted(&encrypted) {
+            let password = self.passphrase.read("Password: ", false)?;
+            crate::process_text_decrypt_password(&mut encrypted.as_slice(), &password)?
+        } else {
+            let key = self
+                .key
+                .ok_or_else(|| anyhow!("--key is required, the input isn't password encrypted"))?;
+            crate::process_text_decrypt(&mut encrypted.as_slice(), &crate::get_content(&key)?)?
+        };
         println!("{}", String::from_utf8_lossy(&decrypted));
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_key_file() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("acli-keys-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let (sk, pk) = (dir.join("ed25519.sk"), dir.join("ed25519.pk"));
+        // an existing world readable file is tightened when overwritten
+        fs::write(&sk, "old")?;
+        write_key_file(&sk, b"secret", true)?;
+        write_key_file(&pk, b"public", false)?;
+        assert_eq!(fs::read(&sk)?, b"secret");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&sk)?.permissions().mode() & 0o777, 0o600);
+            assert_ne!(fs::metadata(&pk)?.permissions().mode() & 0o077, 0);
+        }
+        assert!(write_key_file(&dir.join("missing/ed25519.sk"), b"secret", true).is_err());
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
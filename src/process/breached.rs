@@ -0,0 +1,198 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use md4::Md4;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+
+use crate::BreachHash;
+
+/// Longest line expected in a dataset, `HASH:COUNT` plus line ending.
+const MAX_LINE: usize = 128;
+
+/// A locally downloaded Have I Been Pwned password dataset.
+///
+/// Either the single sorted file produced by the PwnedPasswordsDownloader
+/// (`HASH:COUNT` per line), which is binary searched on disk without loading
+/// it, or a directory of range files named after the 5 character hash prefix
+/// (`5BAA6.txt` with `SUFFIX:COUNT` lines), the format of the range API.
+#[derive(Debug)]
+pub struct BreachDb {
+    path: PathBuf,
+    hash: BreachHash,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BreachReport {
+    pub line: usize,
+    pub count: u64,
+}
+
+impl BreachDb {
+    pub fn open(path: impl AsRef<Path>, hash: BreachHash) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if !path.exists() {
+            return Err(anyhow!("Breach dataset {} does not exist", path.display()));
+        }
+        Ok(Self { path, hash })
+    }
+
+    fn digest(&self, password: &str) -> String {
+        match self.hash {
+            BreachHash::Sha1 => hex::encode_upper(Sha1::digest(password.as_bytes())),
+            BreachHash::Ntlm => {
+                let utf16 = password
+                    .encode_utf16()
+                    .flat_map(|c| c.to_le_bytes())
+                    .collect::<Vec<_>>();
+                hex::encode_upper(Md4::digest(utf16))
+            }
+        }
+    }
+
+    /// How often `password` appears in the dataset, 0 if it doesn't.
+    pub fn count(&self, password: &str) -> Result<u64> {
+        let digest = self.digest(password);
+        let (path, target) = if self.path.is_dir() {
+            let (prefix, suffix) = digest.split_at(5);
+            let path = self.path.join(format!("{}.txt", prefix));
+            if !path.exists() {
+                return Ok(0);
+            }
+            (path, suffix)
+        } else {
+            (self.path.clone(), digest.as_str())
+        };
+        let mut file = File::open(&path)?;
+        let len = file.metadata()?.len();
+        Ok(search(&mut file, len, target.as_bytes())?.unwrap_or(0))
+    }
+
+    pub fn is_breached(&self, password: &str) -> Result<bool> {
+        Ok(self.count(password)? > 0)
+    }
+}
+
+/// Read the first complete line starting at or after `pos`, returning its
+/// offset, length including the line ending, and content.
+fn line_from<R: Read + Seek>(reader: &mut R, pos: u64) -> Result<Option<(u64, usize, Vec<u8>)>> {
+    // start one byte early so a line beginning exactly at `pos` is found
+    let from = pos.saturating_sub(1);
+    reader.seek(SeekFrom::Start(from))?;
+    let mut buf = Vec::with_capacity(MAX_LINE * 2);
+    reader.take((MAX_LINE * 2) as u64).read_to_end(&mut buf)?;
+    let eof = buf.len() < MAX_LINE * 2;
+    let skip = if pos == 0 {
+        0
+    } else {
+        match buf.iter().position(|b| *b == b'\n') {
+            Some(i) => i + 1,
+            None => return Ok(None),
+        }
+    };
+    let rest = &buf[skip..];
+    if rest.is_empty() {
+        return Ok(None);
+    }
+    let (len, line) = match rest.iter().position(|b| *b == b'\n') {
+        Some(i) => (i + 1, &rest[..i]),
+        None if eof => (rest.len(), rest),
+        None => return Err(anyhow!("Breach dataset line at offset {} is too long", pos)),
+    };
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    Ok(Some((from + skip as u64, len, line.to_vec())))
+}
+
+/// Binary search a file of `HASH:COUNT` lines sorted by hash.
+fn search<R: Read + Seek>(reader: &mut R, len: u64, target: &[u8]) -> Result<Option<u64>> {
+    let (mut lo, mut hi) = (0, len);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        let Some((start, line_len, line)) = line_from(reader, mid)? else {
+            hi = mid;
+            continue;
+        };
+        if start >= hi {
+            hi = mid;
+            continue;
+        }
+        let hash = line.split(|b| *b == b':').next().unwrap_or_default();
+        match hash.to_ascii_uppercase().as_slice().cmp(target) {
+            std::cmp::Ordering::Equal => {
+                let count = line
+                    .get(hash.len() + 1..)
+                    .and_then(|c| std::str::from_utf8(c).ok())
+                    .and_then(|c| c.trim().parse().ok())
+                    .unwrap_or(1);
+                return Ok(Some(count));
+            }
+            std::cmp::Ordering::Less => lo = start + line_len as u64,
+            std::cmp::Ordering::Greater => hi = mid,
+        }
+    }
+    Ok(None)
+}
+
+/// Look up each password in `input`, one per line, skipping blank lines.
+pub fn process_breached(input: &str, db: &BreachDb) -> Result<Vec<BreachReport>> {
+    let reader = BufReader::new(crate::get_reader(input)?);
+    let mut ret = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let password = line.trim_end_matches('\r');
+        if password.is_empty() {
+            continue;
+        }
+        ret.push(BreachReport {
+            line: i + 1,
+            count: db.count(password)?,
+        });
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breach_db_sha1() -> Result<()> {
+        let db = BreachDb::open("fixtures/hibp/sha1.txt", BreachHash::Sha1)?;
+        assert_eq!(db.count("password")?, 9545824);
+        assert_eq!(db.count("123456")?, 37359195);
+        assert_eq!(db.count("juventus1897")?, 12);
+        assert_eq!(db.count("q8x-yZZMEsQNc8f2uF4iRtvE")?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_breach_db_every_line() -> Result<()> {
+        // every hash in the file must be found, including the first and last
+        let content = std::fs::read_to_string("fixtures/hibp/sha1.txt")?;
+        let len = content.len() as u64;
+        let mut file = File::open("fixtures/hibp/sha1.txt")?;
+        for line in content.lines() {
+            let (hash, count) = line.split_once(':').expect("HASH:COUNT");
+            assert_eq!(
+                search(&mut file, len, hash.as_bytes())?,
+                Some(count.parse()?)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_breach_db_ntlm_and_range() -> Result<()> {
+        let db = BreachDb::open("fixtures/hibp/ntlm.txt", BreachHash::Ntlm)?;
+        assert!(db.is_breached("password")?);
+        assert!(!db.is_breached("correct horse battery staple")?);
+        let db = BreachDb::open("fixtures/hibp/range", BreachHash::Sha1)?;
+        assert_eq!(db.count("password")?, 9545824);
+        assert_eq!(db.count("123456")?, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,558 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::Write as _,
+    io::Read,
+};
+
+use anyhow::{anyhow, Result};
+use csv::Reader;
+use serde_json::{json, Map, Value};
+
+use crate::{CodegenLang, SampleFormat};
+
+/// Type inferred from one or more sample values.
+#[derive(Debug, Clone, PartialEq)]
+enum FieldType {
+    /// only nulls (or empty CSV cells) were seen
+    Null,
+    Bool,
+    Integer,
+    Float,
+    String,
+    Array(Box<FieldType>),
+    Object(Vec<Field>),
+    /// conflicting types, falls back to an untyped value
+    Any,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Field {
+    name: String,
+    ty: FieldType,
+    optional: bool,
+}
+
+impl FieldType {
+    fn from_value(value: &Value) -> Self {
+        match value {
+            Value::Null => FieldType::Null,
+            Value::Bool(_) => FieldType::Bool,
+            Value::Number(n) if n.is_i64() || n.is_u64() => FieldType::Integer,
+            Value::Number(_) => FieldType::Float,
+            Value::String(_) => FieldType::String,
+            Value::Array(items) => FieldType::Array(Box::new(
+                items
+                    .iter()
+                    .map(FieldType::from_value)
+                    .reduce(FieldType::merge)
+                    .unwrap_or(FieldType::Null),
+            )),
+            Value::Object(map) => FieldType::Object(
+                map.iter()
+                    .map(|(k, v)| Field {
+                        name: k.clone(),
+                        ty: FieldType::from_value(v),
+                        optional: v.is_null(),
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    /// CSV cells are untyped, so guess the narrowest type that parses.
+    fn from_cell(cell: &str) -> Self {
+        if cell.is_empty() {
+            FieldType::Null
+        } else if cell == "true" || cell == "false" {
+            FieldType::Bool
+        } else if cell.parse::<i64>().is_ok() {
+            FieldType::Integer
+        } else if cell.parse::<f64>().is_ok() {
+            FieldType::Float
+        } else {
+            FieldType::String
+        }
+    }
+
+    fn merge(self, other: FieldType) -> FieldType {
+        use FieldType::*;
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Null, t) | (t, Null) => t,
+            (Integer, Float) | (Float, Integer) => Float,
+            (Array(a), Array(b)) => Array(Box::new(a.merge(*b))),
+            (Object(a), Object(b)) => Object(merge_fields(a, b)),
+            _ => Any,
+        }
+    }
+}
+
+fn merge_fields(a: Vec<Field>, b: Vec<Field>) -> Vec<Field> {
+    let mut ret = a;
+    for f in ret.iter_mut() {
+        if !b.iter().any(|o| o.name == f.name) {
+            f.optional = true;
+        }
+    }
+    for f in b {
+        match ret.iter_mut().find(|o| o.name == f.name) {
+            Some(existing) => {
+                existing.optional |= f.optional;
+                existing.ty = existing.ty.clone().merge(f.ty);
+            }
+            None => ret.push(Field {
+                optional: true,
+                ..f
+            }),
+        }
+    }
+    ret
+}
+
+fn infer(samples: &[Value]) -> Result<Vec<Field>> {
+    let ty = samples
+        .iter()
+        .map(FieldType::from_value)
+        .reduce(FieldType::merge)
+        .ok_or_else(|| anyhow!("Sample contains no records"))?;
+    match ty {
+        FieldType::Object(mut fields) => {
+            for f in fields.iter_mut() {
+                f.optional |= f.ty == FieldType::Null;
+            }
+            Ok(fields)
+        }
+        _ => Err(anyhow!("Sample records must be objects")),
+    }
+}
+
+fn infer_csv(reader: Box<dyn Read>) -> Result<Vec<Field>> {
+    let mut reader = Reader::from_reader(reader);
+    let headers = reader.headers()?.clone();
+    let mut fields: Vec<Field> = headers
+        .iter()
+        .map(|h| Field {
+            name: h.to_string(),
+            ty: FieldType::Null,
+            optional: false,
+        })
+        .collect();
+    for result in reader.records() {
+        let record = result?;
+        for (field, cell) in fields.iter_mut().zip(record.iter()) {
+            let ty = FieldType::from_cell(cell);
+            field.optional |= ty == FieldType::Null;
+            field.ty = field.ty.clone().merge(ty);
+        }
+    }
+    Ok(fields)
+}
+
+fn load_samples(content: &str, format: SampleFormat) -> Result<Vec<Value>> {
+    let value: Value = match format {
+        SampleFormat::Json => serde_json::from_str(content)?,
+        SampleFormat::Yaml => serde_yaml::from_str(content)?,
+        SampleFormat::Csv => unreachable!("csv samples are inferred while streaming"),
+    };
+    Ok(match value {
+        Value::Array(items) => items,
+        v => vec![v],
+    })
+}
+
+fn to_pascal_case(s: &str) -> String {
+    s.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| {
+            let mut chars = w.chars();
+            let first = chars.next().unwrap_or_default().to_ascii_uppercase();
+            first.to_string() + chars.as_str()
+        })
+        .collect()
+}
+
+/// `name` as a type name valid in every target language.
+fn type_name(name: &str) -> Result<String> {
+    let ret = to_pascal_case(name);
+    match ret.chars().next() {
+        None => Err(anyhow!(
+            "Type name {:?} has no ASCII letters or digits",
+            name
+        )),
+        Some(c) if c.is_ascii_digit() => {
+            Err(anyhow!("Type name {:?} must start with a letter", name))
+        }
+        _ if ret == "Self" => Err(anyhow!("Self is reserved, pick another type name")),
+        _ => Ok(ret),
+    }
+}
+
+fn to_snake_case(s: &str) -> String {
+    let mut ret = String::new();
+    let mut prev_lower = false;
+    for c in s.chars() {
+        if c.is_ascii_alphanumeric() {
+            if c.is_ascii_uppercase() && prev_lower {
+                ret.push('_');
+            }
+            prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+            ret.push(c.to_ascii_lowercase());
+        } else if !ret.ends_with('_') {
+            ret.push('_');
+            prev_lower = false;
+        }
+    }
+    let ret = ret.trim_matches('_').to_string();
+    match ret.chars().next() {
+        None => "field".to_string(),
+        Some(c) if c.is_ascii_digit() => format!("_{}", ret),
+        // these can't be raw identifiers
+        _ if matches!(ret.as_str(), "self" | "super" | "crate") => format!("{}_", ret),
+        _ if is_rust_keyword(&ret) => format!("r#{}", ret),
+        _ => ret,
+    }
+}
+
+fn is_rust_keyword(s: &str) -> bool {
+    // strict and reserved keywords, either would fail to compile as a field name
+    matches!(
+        s,
+        "abstract"
+            | "as"
+            | "async"
+            | "await"
+            | "become"
+            | "box"
+            | "break"
+            | "const"
+            | "continue"
+            | "do"
+            | "dyn"
+            | "else"
+            | "enum"
+            | "extern"
+            | "false"
+            | "final"
+            | "fn"
+            | "for"
+            | "gen"
+            | "if"
+            | "impl"
+            | "in"
+            | "let"
+            | "loop"
+            | "macro"
+            | "match"
+            | "mod"
+            | "move"
+            | "mut"
+            | "override"
+            | "priv"
+            | "pub"
+            | "ref"
+            | "return"
+            | "static"
+            | "struct"
+            | "trait"
+            | "true"
+            | "try"
+            | "type"
+            | "typeof"
+            | "unsafe"
+            | "unsized"
+            | "use"
+            | "virtual"
+            | "where"
+            | "while"
+            | "yield"
+    )
+}
+
+struct RustGenerator {
+    structs: Vec<String>,
+    names: BTreeSet<String>,
+}
+
+impl RustGenerator {
+    fn generate(name: &str, fields: &[Field]) -> Result<String> {
+        let mut gen = Self {
+            structs: Vec::new(),
+            names: BTreeSet::new(),
+        };
+        gen.emit_struct(&to_pascal_case(name), fields)?;
+        let mut ret = "use serde::{Deserialize, Serialize};\n".to_string();
+        for s in gen.structs.iter().rev() {
+            ret.push('\n');
+            ret.push_str(s);
+        }
+        Ok(ret)
+    }
+
+    fn emit_struct(&mut self, name: &str, fields: &[Field]) -> Result<String> {
+        // avoid clashes between nested structs with the same field name
+        let mut unique = name.to_string();
+        let mut i = 2;
+        while !self.names.insert(unique.clone()) {
+            unique = format!("{}{}", name, i);
+            i += 1;
+        }
+        let mut body = String::new();
+        writeln!(body, "#[derive(Debug, Clone, Serialize, Deserialize)]")?;
+        writeln!(body, "pub struct {} {{", unique)?;
+        // "Name"/"name" or "a-b"/"a_b" map to the same identifier
+        let mut idents = HashSet::new();
+        for f in fields {
+            let base = to_snake_case(&f.name);
+            let mut ident = base.clone();
+            let mut i = 2;
+            while !idents.insert(ident.clone()) {
+                ident = format!("{}_{}", base.trim_start_matches("r#"), i);
+                i += 1;
+            }
+            if ident.trim_start_matches("r#") != f.name {
+                writeln!(body, "    #[serde(rename = {:?})]", f.name)?;
+            }
+            let mut ty = self.rust_type(&unique, &f.name, &f.ty)?;
+            if f.optional {
+                writeln!(
+                    body,
+                    "    #[serde(default, skip_serializing_if = \"Option::is_none\")]"
+                )?;
+                ty = format!("Option<{}>", ty);
+            }
+            writeln!(body, "    pub {}: {},", ident, ty)?;
+        }
+        writeln!(body, "}}")?;
+        self.structs.push(body);
+        Ok(unique)
+    }
+
+    fn rust_type(&mut self, parent: &str, field: &str, ty: &FieldType) -> Result<String> {
+        Ok(match ty {
+            FieldType::Null | FieldType::Any => "serde_json::Value".to_string(),
+            FieldType::Bool => "bool".to_string(),
+            FieldType::Integer => "i64".to_string(),
+            FieldType::Float => "f64".to_string(),
+            FieldType::String => "String".to_string(),
+            FieldType::Array(item) => format!("Vec<{}>", self.rust_type(parent, field, item)?),
+            FieldType::Object(fields) => {
+                self.emit_struct(&format!("{}{}", parent, to_pascal_case(field)), fields)?
+            }
+        })
+    }
+}
+
+fn ts_type(ty: &FieldType, indent: usize) -> String {
+    match ty {
+        FieldType::Null => "null".to_string(),
+        FieldType::Any => "unknown".to_string(),
+        FieldType::Bool => "boolean".to_string(),
+        FieldType::Integer | FieldType::Float => "number".to_string(),
+        FieldType::String => "string".to_string(),
+        FieldType::Array(item) => match item.as_ref() {
+            FieldType::Object(_) => format!("Array<{}>", ts_type(item, indent)),
+            item => format!("{}[]", ts_type(item, indent)),
+        },
+        FieldType::Object(fields) => format!(
+            "{{\n{}{}}}",
+            ts_fields(fields, indent + 1),
+            "  ".repeat(indent)
+        ),
+    }
+}
+
+fn ts_fields(fields: &[Field], indent: usize) -> String {
+    let pad = "  ".repeat(indent);
+    fields
+        .iter()
+        .map(|f| {
+            let is_ident = f
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+                && !f.name.starts_with(|c: char| c.is_ascii_digit());
+            let key = if is_ident && !f.name.is_empty() {
+                f.name.clone()
+            } else {
+                format!("{:?}", f.name)
+            };
+            let optional = if f.optional { "?" } else { "" };
+            format!("{}{}{}: {};\n", pad, key, optional, ts_type(&f.ty, indent))
+        })
+        .collect()
+}
+
+fn json_schema_type(ty: &FieldType) -> Value {
+    match ty {
+        FieldType::Null => json!({ "type": "null" }),
+        FieldType::Any => json!({}),
+        FieldType::Bool => json!({ "type": "boolean" }),
+        FieldType::Integer => json!({ "type": "integer" }),
+        FieldType::Float => json!({ "type": "number" }),
+        FieldType::String => json!({ "type": "string" }),
+        FieldType::Array(item) => json!({ "type": "array", "items": json_schema_type(item) }),
+        FieldType::Object(fields) => json_schema_object(fields),
+    }
+}
+
+fn json_schema_object(fields: &[Field]) -> Value {
+    let mut properties = Map::new();
+    for f in fields {
+        properties.insert(f.name.clone(), json_schema_type(&f.ty));
+    }
+    let required = fields
+        .iter()
+        .filter(|f| !f.optional)
+        .map(|f| Value::String(f.name.clone()))
+        .collect::<Vec<_>>();
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+/// Infer a record schema from `input` and render it in `lang`.
+pub fn process_codegen(
+    input: &str,
+    format: SampleFormat,
+    lang: CodegenLang,
+    name: &str,
+) -> Result<String> {
+    let reader = crate::get_reader(input)?;
+    let fields = match format {
+        SampleFormat::Csv => infer_csv(reader)?,
+        _ => {
+            let mut content = String::new();
+            let mut reader = reader;
+            reader.read_to_string(&mut content)?;
+            infer(&load_samples(&content, format)?)?
+        }
+    };
+    let name = type_name(name)?;
+    let ret = match lang {
+        CodegenLang::Rust => RustGenerator::generate(&name, &fields)?,
+        CodegenLang::TypeScript => {
+            format!(
+                "export interface {} {{\n{}}}\n",
+                name,
+                ts_fields(&fields, 1)
+            )
+        }
+        CodegenLang::JsonSchema => {
+            let mut schema = json!({
+                "$schema": "https://json-schema.org/draft/2020-12/schema",
+                "title": name,
+            });
+            if let (Value::Object(map), Value::Object(object)) =
+                (&mut schema, json_schema_object(&fields))
+            {
+                map.extend(object);
+            }
+            serde_json::to_string_pretty(&schema)? + "\n"
+        }
+    };
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_codegen_rust_from_csv() -> Result<()> {
+        let ret = process_codegen(
+            "assets/juventus.csv",
+            SampleFormat::Csv,
+            CodegenLang::Rust,
+            "player",
+        )?;
+        assert!(ret.contains("pub struct Player {"));
+        assert!(ret.contains("    #[serde(rename = \"DOB\")]\n    pub dob: String,"));
+        assert!(ret.contains("    #[serde(rename = \"Kit Number\")]\n    pub kit_number: i64,"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_codegen_typescript_from_json() -> Result<()> {
+        let ret = process_codegen(
+            "fixtures/codegen/sample.json",
+            SampleFormat::Json,
+            CodegenLang::TypeScript,
+            "Player",
+        )?;
+        assert_eq!(
+            ret,
+            "export interface Player {\n  name: string;\n  \"Kit Number\": number;\n  tags: string[];\n  stats: {\n    goals: number;\n    rating?: number;\n  };\n  loan?: boolean;\n}\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_codegen_json_schema() -> Result<()> {
+        let ret = process_codegen(
+            "fixtures/codegen/sample.json",
+            SampleFormat::Json,
+            CodegenLang::JsonSchema,
+            "Player",
+        )?;
+        let schema: Value = serde_json::from_str(&ret)?;
+        assert_eq!(
+            schema["properties"]["stats"]["properties"]["rating"]["type"],
+            "number"
+        );
+        assert_eq!(
+            schema["required"],
+            json!(["name", "Kit Number", "tags", "stats"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_snake_case() {
+        assert_eq!(to_snake_case("Kit Number"), "kit_number");
+        assert_eq!(to_snake_case("DOB"), "dob");
+        assert_eq!(to_snake_case("firstName"), "first_name");
+        assert_eq!(to_snake_case("type"), "r#type");
+        assert_eq!(to_snake_case("1st"), "_1st");
+        assert_eq!(to_snake_case("self"), "self_");
+        assert_eq!(to_snake_case("Self"), "self_");
+        assert_eq!(to_snake_case("super"), "super_");
+        assert_eq!(to_snake_case("crate"), "crate_");
+        assert_eq!(to_snake_case("yield"), "r#yield");
+        assert_eq!(to_snake_case("try"), "r#try");
+        assert_eq!(to_snake_case("Abstract"), "r#abstract");
+    }
+
+    #[test]
+    fn test_type_name() {
+        assert_eq!(type_name("player row").ok(), Some("PlayerRow".to_string()));
+        assert!(type_name("球员").is_err());
+        assert!(type_name("--").is_err());
+        assert!(type_name("1st").is_err());
+        assert!(type_name("self").is_err());
+    }
+
+    #[test]
+    fn test_process_codegen_rust_duplicate_fields() -> Result<()> {
+        let dir = std::env::temp_dir().join("acli-codegen-dup");
+        std::fs::create_dir_all(&dir)?;
+        let sample = dir.join("sample.json");
+        std::fs::write(
+            &sample,
+            r#"{"Name": "a", "name": "b", "a-b": 1, "a_b": 2, "self": true}"#,
+        )?;
+        let ret = process_codegen(
+            sample.to_str().expect("utf-8 path"),
+            SampleFormat::Json,
+            CodegenLang::Rust,
+            "Row",
+        )?;
+        assert!(ret.contains("    #[serde(rename = \"Name\")]\n    pub name: String,"));
+        assert!(ret.contains("    #[serde(rename = \"name\")]\n    pub name_2: String,"));
+        assert!(ret.contains("    #[serde(rename = \"a-b\")]\n    pub a_b: i64,"));
+        assert!(ret.contains("    #[serde(rename = \"a_b\")]\n    pub a_b_2: i64,"));
+        assert!(ret.contains("    #[serde(rename = \"self\")]\n    pub self_: bool,"));
+        Ok(())
+    }
+}
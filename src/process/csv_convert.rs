@@ -1,11 +1,16 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use csv::Reader;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use std::fs;
 
+use super::{
+    csv_script::RecordScript,
+    xlsx::{is_spreadsheet, read_sheet, write_xlsx},
+};
 use crate::cli::OutputFormat;
 
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 struct Player {
@@ -18,7 +23,38 @@ struct Player {
     kit: u8,
 }
 
-pub fn process_csv(input: &str, output: String, format: OutputFormat) -> Result<()> {
+pub fn process_csv(
+    input: &str,
+    output: String,
+    format: OutputFormat,
+    script: Option<&str>,
+    sheet: Option<&str>,
+) -> Result<()> {
+    let mut script = script.map(RecordScript::load).transpose()?;
+    let rows = if is_spreadsheet(input) {
+        read_sheet(input, sheet)?
+    } else {
+        if sheet.is_some() {
+            return Err(anyhow!("--sheet only applies to spreadsheet input"));
+        }
+        read_csv(input)?
+    };
+    let mut ret = Vec::with_capacity(rows.len());
+    for json_value in rows {
+        // 脚本返回 () 时丢弃该行
+        let json_value = match script.as_mut() {
+            Some(script) => match script.apply(json_value)? {
+                Some(v) => v,
+                None => continue,
+            },
+            None => json_value,
+        };
+        ret.push(Value::Object(json_value));
+    }
+    write_records(&ret, &output, format)
+}
+
+fn read_csv(input: &str) -> Result<Vec<Map<String, Value>>> {
     let mut reader = Reader::from_path(input)?;
     let mut ret = Vec::with_capacity(128);
     let headers = reader.headers()?.clone();
@@ -27,14 +63,50 @@ pub fn process_csv(input: &str, output: String, format: OutputFormat) -> Result<
         // headers.iter() -> 使用 headers 的迭代器
         // record.iter() -> 使用 record 的迭代器
         // zip -> 将两个迭代器合并成一个元组的迭代器 [(header, record), ...]
-        let json_value = headers.iter().zip(record.iter()).collect::<Value>();
-        println!("{:?}", record);
+        let json_value = headers
+            .iter()
+            .zip(record.iter())
+            .map(|(h, v)| (h.to_string(), Value::from(v)))
+            .collect::<Map<_, _>>();
         ret.push(json_value);
     }
+    Ok(ret)
+}
+
+/// Serialize `records` in `format` and write them to `output`.
+pub(crate) fn write_records(records: &[Value], output: &str, format: OutputFormat) -> Result<()> {
     let content = match format {
-        OutputFormat::Json => serde_json::to_string_pretty(&ret)?,
-        OutputFormat::Yaml => serde_yaml::to_string(&ret)?,
+        OutputFormat::Json => serde_json::to_string_pretty(records)?,
+        OutputFormat::Yaml => serde_yaml::to_string(records)?,
+        OutputFormat::Xlsx => return write_xlsx(records, output),
     };
     fs::write(output, content)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_csv_keeps_header_order() -> Result<()> {
+        // serde_json 启用了 preserve_order，字段顺序跟随表头而不是按字母排序
+        let output = std::env::temp_dir().join("acli-csv-convert-order.json");
+        let output = output.to_str().expect("utf-8 path").to_string();
+        process_csv(
+            "assets/juventus.csv",
+            output.clone(),
+            OutputFormat::Json,
+            None,
+            None,
+        )?;
+        let rows: Vec<Map<String, Value>> = serde_json::from_str(&fs::read_to_string(&output)?)?;
+        let keys = rows[0].keys().map(String::as_str).collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            ["Name", "Position", "DOB", "Nationality", "Kit Number"]
+        );
+        assert_eq!(rows[0]["Name"], "Wojciech Szczesny");
+        Ok(())
+    }
+}
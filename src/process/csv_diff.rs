@@ -0,0 +1,242 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use comfy_table::{Cell, Color, Table};
+use csv::{ReaderBuilder, StringRecord};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Keyed differences between two CSV files, in a JSON-patch-like shape.
+#[derive(Debug, Default, Serialize)]
+pub struct CsvDiff {
+    pub key: String,
+    pub columns: Vec<String>,
+    pub changes: Vec<RowChange>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum RowChange {
+    Add {
+        key: String,
+        value: Map<String, Value>,
+    },
+    Remove {
+        key: String,
+        value: Map<String, Value>,
+    },
+    Replace {
+        key: String,
+        cells: Vec<CellChange>,
+    },
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct CellChange {
+    pub column: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+struct KeyedCsv {
+    headers: StringRecord,
+    keys: Vec<String>,
+    rows: HashMap<String, StringRecord>,
+}
+
+impl KeyedCsv {
+    fn load(input: &str, key: &str, delimiter: u8) -> Result<Self> {
+        let mut reader = ReaderBuilder::new()
+            .delimiter(delimiter)
+            .from_reader(crate::get_reader(input)?);
+        let headers = reader.headers()?.clone();
+        let idx = headers
+            .iter()
+            .position(|h| h == key)
+            .ok_or_else(|| anyhow!("Key column {} not found in {}", key, input))?;
+        let mut keys = Vec::new();
+        let mut rows = HashMap::new();
+        for result in reader.records() {
+            let record = result?;
+            let k = record.get(idx).unwrap_or_default().to_string();
+            if rows.contains_key(&k) {
+                return Err(anyhow!("Duplicate key {} in {}", k, input));
+            }
+            keys.push(k.clone());
+            rows.insert(k, record);
+        }
+        Ok(Self {
+            headers,
+            keys,
+            rows,
+        })
+    }
+
+    fn get(&self, record: &StringRecord, column: &str) -> Option<String> {
+        let idx = self.headers.iter().position(|h| h == column)?;
+        record.get(idx).map(|v| v.to_string())
+    }
+
+    fn to_map(&self, record: &StringRecord) -> Map<String, Value> {
+        self.headers
+            .iter()
+            .zip(record.iter())
+            .map(|(h, v)| (h.to_string(), Value::String(v.to_string())))
+            .collect()
+    }
+}
+
+impl CsvDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn to_table(&self) -> Table {
+        let mut table = Table::new();
+        table.set_header(vec!["op", &self.key, "column", "old", "new"]);
+        for change in &self.changes {
+            match change {
+                RowChange::Add { key, value } => {
+                    table.add_row(vec![
+                        Cell::new("+").fg(Color::Green),
+                        Cell::new(key).fg(Color::Green),
+                        Cell::new(""),
+                        Cell::new(""),
+                        Cell::new(self.join_values(value)).fg(Color::Green),
+                    ]);
+                }
+                RowChange::Remove { key, value } => {
+                    table.add_row(vec![
+                        Cell::new("-").fg(Color::Red),
+                        Cell::new(key).fg(Color::Red),
+                        Cell::new(""),
+                        Cell::new(self.join_values(value)).fg(Color::Red),
+                        Cell::new(""),
+                    ]);
+                }
+                RowChange::Replace { key, cells } => {
+                    for cell in cells {
+                        table.add_row(vec![
+                            Cell::new("~").fg(Color::Yellow),
+                            Cell::new(key).fg(Color::Yellow),
+                            Cell::new(&cell.column),
+                            Cell::new(cell.from.as_deref().unwrap_or_default()).fg(Color::Red),
+                            Cell::new(cell.to.as_deref().unwrap_or_default()).fg(Color::Green),
+                        ]);
+                    }
+                }
+            }
+        }
+        table
+    }
+
+    fn join_values(&self, value: &Map<String, Value>) -> String {
+        self.columns
+            .iter()
+            .filter_map(|c| value.get(c).and_then(|v| v.as_str()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+pub fn process_csv_diff(old: &str, new: &str, key: &str, delimiter: u8) -> Result<CsvDiff> {
+    let old = KeyedCsv::load(old, key, delimiter)?;
+    let new = KeyedCsv::load(new, key, delimiter)?;
+
+    // union of both headers, in the order they first appear
+    let mut seen = HashSet::new();
+    let columns = old
+        .headers
+        .iter()
+        .chain(new.headers.iter())
+        .filter(|h| seen.insert(*h))
+        .collect::<Vec<_>>();
+
+    let mut changes = Vec::new();
+    for k in &old.keys {
+        if !new.rows.contains_key(k) {
+            changes.push(RowChange::Remove {
+                key: k.clone(),
+                value: old.to_map(&old.rows[k]),
+            });
+        }
+    }
+    for k in &new.keys {
+        let new_record = &new.rows[k];
+        match old.rows.get(k) {
+            None => changes.push(RowChange::Add {
+                key: k.clone(),
+                value: new.to_map(new_record),
+            }),
+            Some(old_record) => {
+                let cells = columns
+                    .iter()
+                    .filter_map(|column| {
+                        let from = old.get(old_record, column);
+                        let to = new.get(new_record, column);
+                        (from != to).then(|| CellChange {
+                            column: column.to_string(),
+                            from,
+                            to,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                if !cells.is_empty() {
+                    changes.push(RowChange::Replace {
+                        key: k.clone(),
+                        cells,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(CsvDiff {
+        key: key.to_string(),
+        columns: columns.iter().map(|c| c.to_string()).collect(),
+        changes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_csv_diff_same_file() -> Result<()> {
+        let diff = process_csv_diff("assets/juventus.csv", "assets/juventus.csv", "Name", b',')?;
+        assert!(diff.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_diff() -> Result<()> {
+        let diff = process_csv_diff(
+            "fixtures/csv_diff/old.csv",
+            "fixtures/csv_diff/new.csv",
+            "Name",
+            b',',
+        )?;
+        assert_eq!(diff.changes.len(), 3);
+        assert!(matches!(&diff.changes[0], RowChange::Remove { key, .. } if key == "Mattia Perin"));
+        assert_eq!(
+            diff.changes[1],
+            RowChange::Replace {
+                key: "Wojciech Szczesny".to_string(),
+                cells: vec![CellChange {
+                    column: "Kit Number".to_string(),
+                    from: Some("1".to_string()),
+                    to: Some("23".to_string()),
+                }],
+            }
+        );
+        assert!(matches!(&diff.changes[2], RowChange::Add { key, .. } if key == "Arthur Melo"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_diff_missing_key() {
+        let ret = process_csv_diff("assets/juventus.csv", "assets/juventus.csv", "Club", b',');
+        assert!(ret.is_err());
+    }
+}
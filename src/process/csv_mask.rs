@@ -0,0 +1,175 @@
+use anyhow::{anyhow, Result};
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use regex::Regex;
+
+use super::Blake3;
+use crate::{MaskMethod, MaskRule};
+
+const REDACTED: &str = "[REDACTED]";
+const FAKE_UPPER: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const FAKE_LOWER: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
+const FAKE_DIGIT: &[u8] = b"0123456789";
+
+struct Masker {
+    hasher: Option<Blake3>,
+    year: Regex,
+}
+
+impl Masker {
+    fn try_new(key: Option<&[u8]>) -> Result<Self> {
+        Ok(Self {
+            hasher: key.map(Blake3::try_new).transpose()?,
+            year: Regex::new(r"\b(\d{4})\b")?,
+        })
+    }
+
+    fn hasher(&self) -> Result<&Blake3> {
+        self.hasher
+            .as_ref()
+            .ok_or_else(|| anyhow!("hash and fake masks require a blake3 key (--key)"))
+    }
+
+    fn mask(&self, method: MaskMethod, value: &str) -> Result<String> {
+        if value.is_empty() {
+            return Ok(String::new());
+        }
+        let ret = match method {
+            MaskMethod::Hash => {
+                self.hasher()?.keyed_hash(value.as_bytes()).to_hex()[..32].to_string()
+            }
+            MaskMethod::Redact => REDACTED.to_string(),
+            MaskMethod::YearOnly => self
+                .year
+                .captures(value)
+                .map(|c| c[1].to_string())
+                .unwrap_or_default(),
+            MaskMethod::Truncate(n) => value.chars().take(n).collect(),
+            MaskMethod::Fake => self.fake(value)?,
+        };
+        Ok(ret)
+    }
+
+    /// Replace letters and digits with pseudo-random ones of the same class, keeping
+    /// punctuation and character count, so that e.g. emails and phone numbers keep their
+    /// shape. Non-ASCII letters and digits (é, 李, ٣) become ASCII ones as well.
+    /// The replacement is derived from the keyed hash, so it is stable across files.
+    fn fake(&self, value: &str) -> Result<String> {
+        let mut stream = self.hasher()?.keyed_xof(value.as_bytes());
+        let mut ret = String::with_capacity(value.len());
+        let mut buf = [0u8; 1];
+        for c in value.chars() {
+            let charset = if c.is_uppercase() {
+                FAKE_UPPER
+            } else if c.is_numeric() {
+                FAKE_DIGIT
+            } else if c.is_alphanumeric() {
+                FAKE_LOWER
+            } else {
+                ret.push(c);
+                continue;
+            };
+            stream.fill(&mut buf);
+            ret.push(charset[buf[0] as usize % charset.len()] as char);
+        }
+        Ok(ret)
+    }
+}
+
+pub fn process_csv_mask(
+    input: &str,
+    output: &str,
+    rules: &[MaskRule],
+    key: Option<&[u8]>,
+    delimiter: u8,
+) -> Result<()> {
+    let masker = Masker::try_new(key)?;
+    let mut reader = ReaderBuilder::new()
+        .delimiter(delimiter)
+        .from_reader(crate::get_reader(input)?);
+    let mut writer = WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(crate::get_writer(output)?);
+
+    let headers = reader.headers()?.clone();
+    let columns = rules
+        .iter()
+        .map(|rule| {
+            headers
+                .iter()
+                .position(|h| h == rule.column)
+                .map(|idx| (idx, rule.method))
+                .ok_or_else(|| anyhow!("Column {} not found", rule.column))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    writer.write_record(&headers)?;
+
+    let mut record = StringRecord::new();
+    while reader.read_record(&mut record)? {
+        let mut masked = record.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        for (idx, method) in &columns {
+            if let Some(value) = masked.get_mut(*idx) {
+                *value = masker.mask(*method, value)?;
+            }
+        }
+        writer.write_record(&masked)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"q8x-yZZMEsQNc8f2uF4iRtvEuW3Dg7Yb";
+
+    #[test]
+    fn test_mask_is_consistent() -> Result<()> {
+        let masker = Masker::try_new(Some(KEY))?;
+        let a = masker.mask(MaskMethod::Hash, "Mattia Perin")?;
+        let b = masker.mask(MaskMethod::Hash, "Mattia Perin")?;
+        assert_eq!(a, b);
+        assert_eq!(a.len(), 32);
+        assert_ne!(a, masker.mask(MaskMethod::Hash, "Gianluigi Buffon")?);
+        assert_eq!(
+            masker.mask(MaskMethod::Fake, "alice@example.com")?,
+            masker.mask(MaskMethod::Fake, "alice@example.com")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_mask_methods() -> Result<()> {
+        let masker = Masker::try_new(Some(KEY))?;
+        assert_eq!(
+            masker.mask(MaskMethod::YearOnly, "Apr 18, 1990 (29)")?,
+            "1990"
+        );
+        assert_eq!(masker.mask(MaskMethod::Redact, "secret")?, REDACTED);
+        assert_eq!(masker.mask(MaskMethod::Truncate(3), "Poland")?, "Pol");
+        let fake = masker.mask(MaskMethod::Fake, "Ab-12@x.io")?;
+        assert_eq!(fake.len(), 10);
+        assert!(fake[..1].chars().all(|c| c.is_ascii_uppercase()));
+        assert_eq!(&fake[2..3], "-");
+        assert!(fake[3..5].chars().all(|c| c.is_ascii_digit()));
+        assert_eq!(&fake[5..6], "@");
+
+        let value = "Zoë 李小龍-٣٤";
+        let fake = masker.mask(MaskMethod::Fake, value)?;
+        assert_eq!(fake.chars().count(), value.chars().count());
+        assert!(fake.is_ascii());
+        assert_eq!(fake.chars().nth(3), Some(' '));
+        assert_eq!(fake.chars().nth(7), Some('-'));
+        assert!(fake.chars().skip(8).all(|c| c.is_ascii_digit()));
+        assert!(fake.chars().next().is_some_and(|c| c.is_ascii_uppercase()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_mask_hash_requires_key() -> Result<()> {
+        let masker = Masker::try_new(None)?;
+        assert!(masker.mask(MaskMethod::Hash, "value").is_err());
+        assert_eq!(masker.mask(MaskMethod::Redact, "value")?, REDACTED);
+        Ok(())
+    }
+}
@@ -0,0 +1,250 @@
+use std::{cell::RefCell, collections::HashMap, fs, rc::Rc};
+
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use regex::Regex;
+use rhai::{Dynamic, Engine, EvalAltResult, Scope, AST};
+use serde_json::{Map, Value};
+
+/// A compiled rhai script applied to each CSV record.
+///
+/// The script must define `fn transform(record)`, which receives the record as a
+/// map from header to value and returns the (possibly modified) map, or `()` to
+/// drop the record:
+///
+/// ```rhai
+/// fn transform(record) {
+///     if record["Position"] == "Goalkeeper" { return; }
+///     record["DOB"] = parse_date(record["DOB"], "%b %d, %Y");
+///     record
+/// }
+/// ```
+pub struct RecordScript {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+}
+
+impl RecordScript {
+    pub fn try_new(script: &str) -> Result<Self> {
+        let mut engine = Engine::new();
+        register_helpers(&mut engine);
+        let ast = engine
+            .compile(script)
+            .map_err(|e| anyhow!("Failed to compile script: {}", e))?;
+        Ok(Self {
+            engine,
+            ast,
+            scope: Scope::new(),
+        })
+    }
+
+    pub fn load(path: &str) -> Result<Self> {
+        Self::try_new(&fs::read_to_string(path)?)
+    }
+
+    pub fn apply(&mut self, record: Map<String, Value>) -> Result<Option<Map<String, Value>>> {
+        let order = record.keys().cloned().collect::<Vec<_>>();
+        let input = rhai::serde::to_dynamic(record).map_err(|e| anyhow!("{}", e))?;
+        let output: Dynamic = self
+            .engine
+            .call_fn(&mut self.scope, &self.ast, "transform", (input,))
+            .map_err(|e| anyhow!("Script error: {}", e))?;
+        if output.is_unit() {
+            return Ok(None);
+        }
+        if !output.is_map() {
+            return Err(anyhow!(
+                "transform must return a map or (), got {}",
+                output.type_name()
+            ));
+        }
+        let value: Value = rhai::serde::from_dynamic(&output).map_err(|e| anyhow!("{}", e))?;
+        let Value::Object(mut map) = value else {
+            unreachable!("a rhai map always deserializes into an object");
+        };
+        // rhai maps are sorted, restore the original column order and append new columns
+        let mut ret = order
+            .iter()
+            .filter_map(|k| map.shift_remove_entry(k))
+            .collect::<Map<_, _>>();
+        ret.append(&mut map);
+        Ok(Some(ret))
+    }
+}
+
+/// Compiled regexes by pattern, shared by the regex helpers so that a pattern is
+/// compiled once per script rather than once per record.
+#[derive(Default, Clone)]
+struct RegexCache(Rc<RefCell<HashMap<String, Regex>>>);
+
+impl RegexCache {
+    /// The compiled `pattern`; an invalid one fails the script instead of
+    /// quietly not matching on every record.
+    fn get(&self, pattern: &str) -> Result<Regex, Box<EvalAltResult>> {
+        if let Some(re) = self.0.borrow().get(pattern) {
+            return Ok(re.clone());
+        }
+        let re = Regex::new(pattern).map_err(|e| format!("Invalid regex {:?}: {}", pattern, e))?;
+        self.0.borrow_mut().insert(pattern.to_string(), re.clone());
+        Ok(re)
+    }
+}
+
+/// Helpers for the usual cleanup jobs that rhai has no built-ins for.
+fn register_helpers(engine: &mut Engine) {
+    let regexes = RegexCache::default();
+    // parse the leading date in `value` and return it as ISO 8601, or () if it doesn't parse
+    engine.register_fn("parse_date", |value: &str, format: &str| -> Dynamic {
+        NaiveDate::parse_and_remainder(value, format)
+            .map(|(d, _)| Dynamic::from(d.format("%Y-%m-%d").to_string()))
+            .unwrap_or(Dynamic::UNIT)
+    });
+    let cache = regexes.clone();
+    engine.register_fn(
+        "regex_match",
+        move |value: &str, pattern: &str| -> Result<bool, Box<EvalAltResult>> {
+            Ok(cache.get(pattern)?.is_match(value))
+        },
+    );
+    // return capture group `index` of the first match, or () if there is none
+    engine.register_fn(
+        "regex_capture",
+        move |value: &str, pattern: &str, index: i64| -> Result<Dynamic, Box<EvalAltResult>> {
+            let capture = regexes
+                .get(pattern)?
+                .captures(value)
+                .and_then(|c| c.get(index as usize))
+                .map(|m| Dynamic::from(m.as_str().to_string()));
+            Ok(capture.unwrap_or(Dynamic::UNIT))
+        },
+    );
+}
+
+fn to_cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+/// Stream `input` through `script` and write the resulting records as CSV.
+///
+/// The output header is taken from the first record the script returns, so
+/// scripts may add or remove columns as long as they do so consistently.
+pub fn process_csv_map(input: &str, output: &str, script: &str, delimiter: u8) -> Result<()> {
+    let mut script = RecordScript::load(script)?;
+    let mut reader = ReaderBuilder::new()
+        .delimiter(delimiter)
+        .from_reader(crate::get_reader(input)?);
+    let mut writer = WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(crate::get_writer(output)?);
+    let headers = reader.headers()?.clone();
+
+    let mut columns: Option<Vec<String>> = None;
+    let mut record = StringRecord::new();
+    while reader.read_record(&mut record)? {
+        let map = headers
+            .iter()
+            .zip(record.iter())
+            .map(|(h, v)| (h.to_string(), Value::from(v)))
+            .collect();
+        let Some(mut map) = script.apply(map)? else {
+            continue;
+        };
+        let columns = match &columns {
+            Some(columns) => columns,
+            None => {
+                let keys = map.keys().cloned().collect::<Vec<_>>();
+                writer.write_record(&keys)?;
+                columns.insert(keys)
+            }
+        };
+        let row = columns
+            .iter()
+            .map(|c| map.remove(c).as_ref().map(to_cell).unwrap_or_default())
+            .collect::<Vec<_>>();
+        if let Some(extra) = map.keys().next() {
+            let line = record.position().map(|p| p.line()).unwrap_or_default();
+            return Err(anyhow!(
+                "line {}: transform added column {} missing from the first record",
+                line,
+                extra
+            ));
+        }
+        writer.write_record(&row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_record_script_transform() -> Result<()> {
+        let mut script = RecordScript::load("fixtures/csv_map/transform.rhai")?;
+        let record = json!({
+            "Name": "Wojciech Szczesny",
+            "Position": "Goalkeeper",
+            "DOB": "Apr 18, 1990 (29)",
+        });
+        let Value::Object(record) = record else {
+            unreachable!()
+        };
+        let ret = script.apply(record)?.expect("record is kept");
+        assert_eq!(ret["DOB"], json!("1990-04-18"));
+        assert_eq!(ret["Age"], json!(29));
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_script_drop() -> Result<()> {
+        let mut script = RecordScript::try_new("fn transform(record) { () }")?;
+        assert!(script.apply(Map::new())?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_script_invalid_return() -> Result<()> {
+        let mut script = RecordScript::try_new("fn transform(record) { 42 }")?;
+        assert!(script.apply(Map::new()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_regex_helpers_cache_patterns() -> Result<()> {
+        let mut script = RecordScript::try_new(
+            r#"fn transform(record) {
+                record["Year"] = regex_capture(record["DOB"], "(\\d{4})", 1);
+                record["Valid"] = regex_match(record["DOB"], "(\\d{4})");
+                record
+            }"#,
+        )?;
+        for _ in 0..3 {
+            let mut record = Map::new();
+            record.insert("DOB".to_string(), Value::from("Apr 18, 1990 (29)"));
+            let ret = script.apply(record)?.expect("record is kept");
+            assert_eq!(ret["Year"], Value::from("1990"));
+            assert_eq!(ret["Valid"], Value::from(true));
+        }
+
+        let cache = RegexCache::default();
+        assert!(cache.get("a+").is_ok_and(|re| re.is_match("caa")));
+        assert!(cache.get("a+").is_ok());
+        assert_eq!(cache.0.borrow().len(), 1);
+        assert!(cache.get("(").is_err());
+
+        // a typo in a pattern fails the record instead of silently not matching
+        let mut script =
+            RecordScript::try_new(r#"fn transform(record) { regex_match("x", "("); record }"#)?;
+        let err = script.apply(Map::new()).unwrap_err();
+        assert!(err.to_string().contains("Invalid regex"));
+        Ok(())
+    }
+}
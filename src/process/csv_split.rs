@@ -0,0 +1,361 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use csv::{ByteRecord, ReaderBuilder, WriterBuilder};
+
+use crate::SplitMode;
+
+/// Column mode keeps at most this many chunk files open, closing the least
+/// recently written one and reopening it in append mode when needed.
+const MAX_OPEN_CHUNKS: usize = 64;
+
+struct Chunk {
+    path: PathBuf,
+    writer: Option<BufWriter<File>>,
+    rows: usize,
+    bytes: u64,
+    last_used: u64,
+}
+
+struct ChunkNamer<'a> {
+    dir: &'a Path,
+    template: &'a str,
+    stem: String,
+}
+
+impl Chunk {
+    fn create(path: PathBuf, header: &[u8]) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(&path)?);
+        writer.write_all(header)?;
+        Ok(Self {
+            path,
+            writer: Some(writer),
+            rows: 0,
+            bytes: header.len() as u64,
+            last_used: 0,
+        })
+    }
+
+    fn write(&mut self, record: &[u8]) -> Result<()> {
+        let writer = match self.writer.as_mut() {
+            Some(writer) => writer,
+            None => {
+                let file = OpenOptions::new().append(true).open(&self.path)?;
+                self.writer.insert(BufWriter::new(file))
+            }
+        };
+        writer.write_all(record)?;
+        self.rows += 1;
+        self.bytes += record.len() as u64;
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn is_full(&self, mode: &SplitMode, next: usize) -> bool {
+        match mode {
+            SplitMode::Rows(n) => self.rows >= *n,
+            // a single oversized row still gets a chunk of its own
+            SplitMode::Bytes(n) => self.rows > 0 && self.bytes + next as u64 > *n,
+            SplitMode::Column(_) => false,
+        }
+    }
+}
+
+impl<'a> ChunkNamer<'a> {
+    /// The path of a new chunk, which must not be one already produced: values that
+    /// sanitize alike ("A/B" and "A_B") or a template without `{index}` would
+    /// otherwise silently overwrite earlier chunks.
+    fn name(&self, used: &mut HashSet<PathBuf>, index: usize, value: &str) -> Result<PathBuf> {
+        let name = self
+            .template
+            .replace("{stem}", &self.stem)
+            .replace("{index}", &index.to_string())
+            .replace("{value}", &sanitize(value));
+        let path = self.dir.join(name);
+        if !used.insert(path.clone()) {
+            return Err(anyhow!(
+                "Chunk {} would overwrite {}, add {{index}} to the template",
+                index,
+                path.display()
+            ));
+        }
+        Ok(path)
+    }
+}
+
+/// Make a column value safe to use as part of a file name.
+fn sanitize(value: &str) -> String {
+    let ret: String = value
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if ret.is_empty() {
+        "empty".to_string()
+    } else {
+        ret
+    }
+}
+
+fn encode(record: &ByteRecord, delimiter: u8) -> Result<Vec<u8>> {
+    let mut writer = WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(Vec::new());
+    writer.write_byte_record(record)?;
+    writer
+        .into_inner()
+        .map_err(|e| anyhow!("Failed to encode record: {}", e))
+}
+
+/// Split `input` into chunks written to `dir`, repeating the header in each chunk.
+///
+/// `template` names the output files and may contain `{stem}` (input file stem),
+/// `{index}` (1-based chunk number) and `{value}` (column value, in column mode).
+/// Records are streamed, so only the open chunk files are kept in memory.
+pub fn process_csv_split(
+    input: &str,
+    mode: &SplitMode,
+    dir: &Path,
+    template: &str,
+    delimiter: u8,
+) -> Result<Vec<PathBuf>> {
+    split(input, mode, dir, template, delimiter, MAX_OPEN_CHUNKS)
+}
+
+fn split(
+    input: &str,
+    mode: &SplitMode,
+    dir: &Path,
+    template: &str,
+    delimiter: u8,
+    max_open: usize,
+) -> Result<Vec<PathBuf>> {
+    if matches!(mode, SplitMode::Rows(0) | SplitMode::Bytes(0)) {
+        return Err(anyhow!("Chunk size must be greater than 0"));
+    }
+    let mut reader = ReaderBuilder::new()
+        .delimiter(delimiter)
+        .from_reader(crate::get_reader(input)?);
+    let headers = reader.byte_headers()?.clone();
+    let header = encode(&headers, delimiter)?;
+    let column = match mode {
+        SplitMode::Column(name) => Some(
+            headers
+                .iter()
+                .position(|h| h == name.as_bytes())
+                .ok_or_else(|| anyhow!("Column {} not found", name))?,
+        ),
+        _ => None,
+    };
+    let stem = Path::new(input)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .filter(|_| input != "-")
+        .unwrap_or("stdin")
+        .to_string();
+    let namer = ChunkNamer {
+        dir,
+        template,
+        stem,
+    };
+
+    let mut files = Vec::new();
+    let mut used = HashSet::new();
+    let mut current: Option<Chunk> = None;
+    let mut by_value: HashMap<Vec<u8>, Chunk> = HashMap::new();
+    let mut open = 0;
+    let mut record = ByteRecord::new();
+    for tick in 1.. {
+        if !reader.read_byte_record(&mut record)? {
+            break;
+        }
+        let bytes = encode(&record, delimiter)?;
+        let chunk = match column {
+            Some(idx) => {
+                let value = record.get(idx).unwrap_or_default();
+                let is_open = by_value.get(value).map(|c| c.writer.is_some());
+                if is_open != Some(true) {
+                    if open >= max_open {
+                        close_least_recent(&mut by_value)?;
+                        open -= 1;
+                    }
+                    open += 1;
+                }
+                if is_open.is_none() {
+                    let path = namer.name(
+                        &mut used,
+                        by_value.len() + 1,
+                        &String::from_utf8_lossy(value),
+                    )?;
+                    files.push(path.clone());
+                    by_value.insert(value.to_vec(), Chunk::create(path, &header)?);
+                }
+                by_value.get_mut(value).expect("chunk was just inserted")
+            }
+            None => {
+                if current
+                    .as_ref()
+                    .is_none_or(|c| c.is_full(mode, bytes.len()))
+                {
+                    if let Some(mut chunk) = current.take() {
+                        chunk.close()?;
+                    }
+                    let path = namer.name(&mut used, files.len() + 1, "")?;
+                    files.push(path.clone());
+                    current = Some(Chunk::create(path, &header)?);
+                }
+                current.as_mut().expect("chunk was just created")
+            }
+        };
+        chunk.last_used = tick;
+        chunk.write(&bytes)?;
+    }
+    for chunk in current.iter_mut().chain(by_value.values_mut()) {
+        chunk.close()?;
+    }
+    Ok(files)
+}
+
+fn close_least_recent(chunks: &mut HashMap<Vec<u8>, Chunk>) -> Result<()> {
+    if let Some(chunk) = chunks
+        .values_mut()
+        .filter(|c| c.writer.is_some())
+        .min_by_key(|c| c.last_used)
+    {
+        chunk.close()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> Result<PathBuf> {
+        let dir = std::env::temp_dir().join(format!("acli-split-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    #[test]
+    fn test_process_csv_split_rows() -> Result<()> {
+        let dir = temp_dir("rows")?;
+        let files = process_csv_split(
+            "assets/juventus.csv",
+            &SplitMode::Rows(10),
+            &dir,
+            "{stem}-{index}.csv",
+            b',',
+        )?;
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0], dir.join("juventus-1.csv"));
+        let last = fs::read_to_string(&files[2])?;
+        assert!(last.starts_with("Name,Position,DOB,Nationality,Kit Number\n"));
+        assert_eq!(last.lines().count(), 8);
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_split_bytes() -> Result<()> {
+        let dir = temp_dir("bytes")?;
+        let files = process_csv_split(
+            "assets/juventus.csv",
+            &SplitMode::Bytes(512),
+            &dir,
+            "{stem}-{index}.csv",
+            b',',
+        )?;
+        assert!(files.len() > 1);
+        for file in &files {
+            assert!(fs::metadata(file)?.len() <= 512);
+        }
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_split_column() -> Result<()> {
+        let dir = temp_dir("column")?;
+        let files = process_csv_split(
+            "assets/juventus.csv",
+            &SplitMode::Column("Position".to_string()),
+            &dir,
+            "{value}.csv",
+            b',',
+        )?;
+        assert_eq!(files.len(), 10);
+        let keepers = fs::read_to_string(dir.join("Goalkeeper.csv"))?;
+        assert_eq!(keepers.lines().count(), 5);
+        assert!(dir.join("Central_Midfield.csv").exists());
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_split_column_reopens_chunks() -> Result<()> {
+        let dir = temp_dir("reopen")?;
+        let files = split(
+            "assets/juventus.csv",
+            &SplitMode::Column("Position".to_string()),
+            &dir,
+            "{value}.csv",
+            b',',
+            2,
+        )?;
+        assert_eq!(files.len(), 10);
+        let mut rows = 0;
+        for file in &files {
+            let content = fs::read_to_string(file)?;
+            assert_eq!(
+                content.lines().next(),
+                Some("Name,Position,DOB,Nationality,Kit Number")
+            );
+            rows += content.lines().count() - 1;
+        }
+        assert_eq!(rows, 27);
+        assert_eq!(
+            fs::read_to_string(dir.join("Goalkeeper.csv"))?
+                .lines()
+                .count(),
+            5
+        );
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_split_duplicate_paths() -> Result<()> {
+        let dir = temp_dir("duplicate")?;
+        let input = dir.join("input.csv");
+        fs::write(&input, "id,group\n1,A/B\n2,A_B\n")?;
+        let input = input.to_str().expect("utf-8 path");
+        let column = SplitMode::Column("group".to_string());
+        assert!(split(input, &column, &dir, "{value}.csv", b',', 8).is_err());
+        let files = split(input, &column, &dir, "{index}-{value}.csv", b',', 8)?;
+        assert_eq!(files, [dir.join("1-A_B.csv"), dir.join("2-A_B.csv")]);
+
+        assert!(process_csv_split(input, &SplitMode::Rows(1), &dir, "part.csv", b',').is_err());
+        assert!(process_csv_split(input, &column, &dir, "{stem}.csv", b',').is_err());
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,391 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use csv::{ReaderBuilder, StringRecord};
+use rusqlite::{params_from_iter, types::ValueRef, Connection};
+use serde_json::{Map, Value};
+
+use super::csv_convert::write_records;
+use crate::{OutputFormat, SqliteMode};
+
+/// Rows read up front to infer column types before the table is created.
+const SAMPLE_ROWS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColumnType {
+    Integer,
+    Real,
+    Text,
+}
+
+impl ColumnType {
+    fn infer<'a>(values: impl Iterator<Item = &'a str>) -> Self {
+        let mut values = values.filter(|v| !v.is_empty()).peekable();
+        if values.peek().is_none() {
+            return ColumnType::Text;
+        }
+        let mut ret = ColumnType::Integer;
+        for v in values {
+            if ret == ColumnType::Integer && v.parse::<i64>().is_err() {
+                ret = ColumnType::Real;
+            }
+            if ret == ColumnType::Real && v.parse::<f64>().is_err() {
+                return ColumnType::Text;
+            }
+        }
+        ret
+    }
+
+    fn as_sql(&self) -> &'static str {
+        match self {
+            ColumnType::Integer => "INTEGER",
+            ColumnType::Real => "REAL",
+            ColumnType::Text => "TEXT",
+        }
+    }
+
+    /// Convert a cell for binding, falling back to text when it doesn't parse.
+    fn to_sql(self, value: &str) -> rusqlite::types::Value {
+        use rusqlite::types::Value as Sql;
+        if value.is_empty() {
+            return Sql::Null;
+        }
+        match self {
+            ColumnType::Integer => value.parse().map(Sql::Integer).ok(),
+            ColumnType::Real => value.parse().map(Sql::Real).ok(),
+            ColumnType::Text => None,
+        }
+        .unwrap_or_else(|| Sql::Text(value.to_string()))
+    }
+}
+
+fn quote(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+fn insert_batch(
+    conn: &mut Connection,
+    sql: &str,
+    types: &[ColumnType],
+    rows: &[StringRecord],
+) -> Result<()> {
+    let tx = conn.transaction()?;
+    {
+        let mut stmt = tx.prepare_cached(sql)?;
+        for row in rows {
+            let values = types
+                .iter()
+                .enumerate()
+                .map(|(i, ty)| ty.to_sql(row.get(i).unwrap_or_default()));
+            stmt.execute(params_from_iter(values))?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Load `input` into `table` of the SQLite database at `db`.
+///
+/// Column types are inferred from the first rows; cells that don't fit are
+/// still stored, as text, thanks to SQLite's dynamic typing. Rows are inserted
+/// in transactions of `batch_size`. Returns the number of rows inserted.
+pub fn process_csv_to_sqlite(
+    input: &str,
+    db: &str,
+    table: &str,
+    mode: SqliteMode,
+    batch_size: usize,
+    delimiter: u8,
+) -> Result<usize> {
+    let batch_size = batch_size.max(1);
+    let mut reader = ReaderBuilder::new()
+        .delimiter(delimiter)
+        .from_reader(crate::get_reader(input)?);
+    let headers = reader.headers()?.clone();
+
+    let mut records = reader.records();
+    let mut batch = Vec::with_capacity(SAMPLE_ROWS.max(batch_size));
+    for record in records.by_ref().take(SAMPLE_ROWS.max(batch_size)) {
+        batch.push(record?);
+    }
+    let types = (0..headers.len())
+        .map(|i| ColumnType::infer(batch.iter().map(|r| r.get(i).unwrap_or_default())))
+        .collect::<Vec<_>>();
+
+    let mut conn = Connection::open(db)?;
+    let exists = table_exists(&conn, table)?;
+    if mode == SqliteMode::Create && exists {
+        return Err(anyhow!(
+            "Table {} already exists, use --append or --replace",
+            table
+        ));
+    }
+    // --replace loads a staging table and swaps it in at the end, so a failed
+    // import leaves the existing table untouched
+    let target = match mode {
+        SqliteMode::Replace => format!("{}_acli_replace", table),
+        _ => table.to_string(),
+    };
+    // never drop a table acli didn't create, even one left by an interrupted run
+    if mode == SqliteMode::Replace && table_exists(&conn, &target)? {
+        return Err(anyhow!(
+            "Staging table {} already exists, drop or rename it to use --replace",
+            target
+        ));
+    }
+    if !exists || mode == SqliteMode::Replace {
+        let columns = headers
+            .iter()
+            .zip(types.iter())
+            .map(|(h, ty)| format!("{} {}", quote(h), ty.as_sql()))
+            .collect::<Vec<_>>();
+        conn.execute(
+            &format!("CREATE TABLE {} ({})", quote(&target), columns.join(", ")),
+            [],
+        )?;
+    }
+
+    let sql = format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        quote(&target),
+        headers.iter().map(quote).collect::<Vec<_>>().join(", "),
+        vec!["?"; headers.len()].join(", ")
+    );
+    let count = match insert_all(&mut conn, &sql, &types, batch, records, batch_size) {
+        Ok(count) => count,
+        Err(e) if mode == SqliteMode::Replace => {
+            // best effort, the import error is the one worth reporting
+            conn.execute(&format!("DROP TABLE IF EXISTS {}", quote(&target)), [])
+                .ok();
+            return Err(e);
+        }
+        Err(e) => return Err(e),
+    };
+    if mode == SqliteMode::Replace {
+        let tx = conn.transaction()?;
+        tx.execute(&format!("DROP TABLE IF EXISTS {}", quote(table)), [])?;
+        tx.execute(
+            &format!("ALTER TABLE {} RENAME TO {}", quote(&target), quote(table)),
+            [],
+        )?;
+        tx.commit()?;
+    }
+    Ok(count)
+}
+
+fn table_exists(conn: &Connection, table: &str) -> Result<bool> {
+    Ok(conn.query_row(
+        "SELECT count(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [table],
+        |row| row.get(0),
+    )?)
+}
+
+/// Insert the sampled `batch` and then the remaining `records`, `batch_size`
+/// rows per transaction.
+fn insert_all(
+    conn: &mut Connection,
+    sql: &str,
+    types: &[ColumnType],
+    mut batch: Vec<StringRecord>,
+    records: impl Iterator<Item = csv::Result<StringRecord>>,
+    batch_size: usize,
+) -> Result<usize> {
+    let mut count = 0;
+    for chunk in batch.chunks(batch_size) {
+        insert_batch(conn, sql, types, chunk)?;
+        count += chunk.len();
+    }
+    batch.clear();
+    for record in records {
+        batch.push(record?);
+        if batch.len() == batch_size {
+            insert_batch(conn, sql, types, &batch)?;
+            count += batch.len();
+            batch.clear();
+        }
+    }
+    insert_batch(conn, sql, types, &batch)?;
+    count += batch.len();
+    Ok(count)
+}
+
+fn to_json(value: ValueRef) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::from(i),
+        ValueRef::Real(f) => Value::from(f),
+        ValueRef::Text(t) => Value::from(String::from_utf8_lossy(t).into_owned()),
+        ValueRef::Blob(b) => Value::from(STANDARD.encode(b)),
+    }
+}
+
+/// Run `query` against `db` and write the rows through the `csv convert` writers.
+pub fn process_sqlite_export(
+    db: &str,
+    query: &str,
+    output: &str,
+    format: OutputFormat,
+) -> Result<usize> {
+    let conn = Connection::open(db)?;
+    let mut stmt = conn.prepare(query)?;
+    let columns = stmt
+        .column_names()
+        .into_iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>();
+    let mut rows = stmt.query([])?;
+    let mut ret = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut map = Map::new();
+        for (i, c) in columns.iter().enumerate() {
+            map.insert(c.clone(), to_json(row.get_ref(i)?));
+        }
+        ret.push(Value::Object(map));
+    }
+    write_records(&ret, output, format)?;
+    Ok(ret.len())
+}
+
+/// `SELECT *` for a table name, for exporting a whole table.
+pub fn sqlite_table_query(table: &str) -> String {
+    format!("SELECT * FROM {}", quote(table))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_db(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("acli-{}-{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path.display().to_string()
+    }
+
+    #[test]
+    fn test_column_type_infer() {
+        assert_eq!(
+            ColumnType::infer(["1", "", "37"].into_iter()),
+            ColumnType::Integer
+        );
+        assert_eq!(
+            ColumnType::infer(["1", "2.5"].into_iter()),
+            ColumnType::Real
+        );
+        assert_eq!(
+            ColumnType::infer(["1", "Italy"].into_iter()),
+            ColumnType::Text
+        );
+    }
+
+    #[test]
+    fn test_process_csv_to_sqlite() -> Result<()> {
+        let db = temp_db("to-sqlite");
+        let count = process_csv_to_sqlite(
+            "assets/juventus.csv",
+            &db,
+            "players",
+            SqliteMode::Create,
+            10,
+            b',',
+        )?;
+        assert_eq!(count, 27);
+
+        // a second import must be explicit about what to do with the table
+        let ret = process_csv_to_sqlite(
+            "assets/juventus.csv",
+            &db,
+            "players",
+            SqliteMode::Create,
+            10,
+            b',',
+        );
+        assert!(ret.is_err());
+        process_csv_to_sqlite(
+            "assets/juventus.csv",
+            &db,
+            "players",
+            SqliteMode::Append,
+            10,
+            b',',
+        )?;
+
+        let conn = Connection::open(&db)?;
+        let (count, kit): (i64, i64) = conn.query_row(
+            "SELECT count(*), sum(\"Kit Number\") FROM players WHERE Position = 'Goalkeeper'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        assert_eq!(count, 8);
+        assert_eq!(kit, 2 * (1 + 37 + 77 + 31));
+
+        process_csv_to_sqlite(
+            "assets/juventus.csv",
+            &db,
+            "players",
+            SqliteMode::Replace,
+            10,
+            b',',
+        )?;
+        let count: i64 = conn.query_row("SELECT count(*) FROM players", [], |row| row.get(0))?;
+        assert_eq!(count, 27);
+
+        // a failed --replace keeps the old table and drops the staging one
+        let broken = std::env::temp_dir().join(format!("acli-broken-{}.csv", std::process::id()));
+        let broken = broken.display().to_string();
+        std::fs::write(&broken, "Name,Position\nA,Goalkeeper\nB,Defender,extra\n")?;
+        let ret = process_csv_to_sqlite(&broken, &db, "players", SqliteMode::Replace, 1, b',');
+        assert!(ret.is_err());
+        let count: i64 = conn.query_row("SELECT count(*) FROM players", [], |row| row.get(0))?;
+        assert_eq!(count, 27);
+        let tables: i64 =
+            conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| row.get(0))?;
+        assert_eq!(tables, 1);
+        std::fs::remove_file(broken)?;
+
+        // a user table that happens to have the staging name is left alone
+        conn.execute("CREATE TABLE players_acli_replace (id INTEGER)", [])?;
+        conn.execute("INSERT INTO players_acli_replace VALUES (1)", [])?;
+        let ret = process_csv_to_sqlite(
+            "assets/juventus.csv",
+            &db,
+            "players",
+            SqliteMode::Replace,
+            10,
+            b',',
+        );
+        assert!(ret.is_err());
+        let count: i64 =
+            conn.query_row("SELECT count(*) FROM players_acli_replace", [], |row| {
+                row.get(0)
+            })?;
+        assert_eq!(count, 1);
+        std::fs::remove_file(db)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_sqlite_export() -> Result<()> {
+        let db = temp_db("export");
+        process_csv_to_sqlite(
+            "assets/juventus.csv",
+            &db,
+            "players",
+            SqliteMode::Create,
+            100,
+            b',',
+        )?;
+        let output = format!("{}.json", db);
+        let count = process_sqlite_export(
+            &db,
+            "SELECT Name, \"Kit Number\" FROM players ORDER BY \"Kit Number\" LIMIT 2",
+            &output,
+            OutputFormat::Json,
+        )?;
+        assert_eq!(count, 2);
+        let ret: Value = serde_json::from_str(&std::fs::read_to_string(&output)?)?;
+        assert_eq!(ret[0]["Kit Number"], Value::from(1));
+        std::fs::remove_file(db)?;
+        std::fs::remove_file(output)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,479 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+};
+
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+use csv::{ReaderBuilder, StringRecord};
+use jsonschema::error::ValidationErrorKind;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Column contract for `csv validate`, loaded from a YAML (or JSON) file.
+///
+/// ```yaml
+/// columns:
+///   - name: Kit Number
+///     type: integer
+///     required: true
+///     unique: true
+///     min: 1
+///     max: 99
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct CsvSchema {
+    pub columns: Vec<ColumnSchema>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColumnSchema {
+    pub name: String,
+    #[serde(default, rename = "type")]
+    pub ty: ColumnType,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub unique: bool,
+    pub pattern: Option<String>,
+    #[serde(rename = "enum")]
+    pub values: Option<Vec<String>>,
+    pub min: Option<serde_yaml::Value>,
+    pub max: Option<serde_yaml::Value>,
+    /// chrono format string used by `type: date`, defaults to `%Y-%m-%d`
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    #[default]
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Date,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Violation {
+    /// 1-based line number in the file, the header is line 1
+    pub line: u64,
+    /// 1-based column number, 0 when the violation concerns the whole row
+    pub column: usize,
+    pub name: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {} ({}): {}",
+            self.line, self.column, self.name, self.message
+        )
+    }
+}
+
+trait RowValidator {
+    fn validate(&mut self, line: u64, record: &StringRecord) -> Vec<Violation>;
+}
+
+struct CompiledColumn {
+    schema: ColumnSchema,
+    index: Option<usize>,
+    pattern: Option<Regex>,
+    range: (Option<Bound>, Option<Bound>),
+    seen: HashSet<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Bound {
+    Number(f64),
+    Date(NaiveDate),
+}
+
+struct NativeValidator {
+    columns: Vec<CompiledColumn>,
+}
+
+struct JsonSchemaValidator {
+    headers: StringRecord,
+    types: HashMap<String, String>,
+    validator: jsonschema::Validator,
+}
+
+impl CompiledColumn {
+    fn try_new(schema: ColumnSchema, headers: &StringRecord) -> Result<Self> {
+        let index = headers.iter().position(|h| h == schema.name);
+        let pattern = schema
+            .pattern
+            .as_deref()
+            .map(|p| Regex::new(&format!("^(?:{})$", p)))
+            .transpose()?;
+        let min = schema
+            .min
+            .as_ref()
+            .map(|v| Self::bound(&schema, v))
+            .transpose()?;
+        let max = schema
+            .max
+            .as_ref()
+            .map(|v| Self::bound(&schema, v))
+            .transpose()?;
+        Ok(Self {
+            schema,
+            index,
+            pattern,
+            range: (min, max),
+            seen: HashSet::new(),
+        })
+    }
+
+    fn bound(schema: &ColumnSchema, value: &serde_yaml::Value) -> Result<Bound> {
+        let raw = match value {
+            serde_yaml::Value::String(s) => s.clone(),
+            serde_yaml::Value::Number(n) => n.to_string(),
+            _ => return Err(anyhow!("Invalid min/max for column {}", schema.name)),
+        };
+        Self::parse(schema, &raw).map_err(|e| anyhow!("Column {}: {}", schema.name, e))
+    }
+
+    /// Parse a cell into a comparable bound, checking it against the column type.
+    fn parse(schema: &ColumnSchema, value: &str) -> Result<Bound, String> {
+        match schema.ty {
+            ColumnType::Integer => value
+                .parse::<i64>()
+                .map(|v| Bound::Number(v as f64))
+                .map_err(|_| format!("{:?} is not an integer", value)),
+            ColumnType::Float => value
+                .parse::<f64>()
+                .map(Bound::Number)
+                .map_err(|_| format!("{:?} is not a number", value)),
+            ColumnType::Date => {
+                let format = schema.format.as_deref().unwrap_or("%Y-%m-%d");
+                NaiveDate::parse_from_str(value, format)
+                    .map(Bound::Date)
+                    .map_err(|_| format!("{:?} is not a date in format {:?}", value, format))
+            }
+            ColumnType::Boolean | ColumnType::String => {
+                Err(format!("{:?} columns have no range", schema.ty))
+            }
+        }
+    }
+
+    fn check(&mut self, value: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        if value.is_empty() {
+            if self.schema.required {
+                errors.push("value is required".to_string());
+            }
+            return errors;
+        }
+        match self.schema.ty {
+            ColumnType::String => {}
+            ColumnType::Boolean => {
+                if !matches!(value, "true" | "false") {
+                    errors.push(format!("{:?} is not a boolean", value));
+                }
+            }
+            _ => match Self::parse(&self.schema, value) {
+                Ok(v) => {
+                    if let Some(min) = self.range.0.filter(|min| v < *min) {
+                        errors.push(format!("{:?} is less than {}", value, min));
+                    }
+                    if let Some(max) = self.range.1.filter(|max| v > *max) {
+                        errors.push(format!("{:?} is greater than {}", value, max));
+                    }
+                }
+                Err(e) => errors.push(e),
+            },
+        }
+        if let Some(values) = &self.schema.values {
+            if !values.iter().any(|v| v == value) {
+                errors.push(format!("{:?} is not one of {:?}", value, values));
+            }
+        }
+        if let Some(pattern) = &self.pattern {
+            if !pattern.is_match(value) {
+                errors.push(format!("{:?} does not match {:?}", value, pattern.as_str()));
+            }
+        }
+        if self.schema.unique && !self.seen.insert(value.to_string()) {
+            errors.push(format!("{:?} is a duplicate", value));
+        }
+        errors
+    }
+}
+
+impl fmt::Display for Bound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bound::Number(n) => write!(f, "{}", n),
+            Bound::Date(d) => write!(f, "{}", d),
+        }
+    }
+}
+
+impl NativeValidator {
+    fn try_new(schema: CsvSchema, headers: &StringRecord) -> Result<Self> {
+        let columns = schema
+            .columns
+            .into_iter()
+            .map(|c| CompiledColumn::try_new(c, headers))
+            .collect::<Result<_>>()?;
+        Ok(Self { columns })
+    }
+
+    fn check_headers(&self) -> Vec<Violation> {
+        self.columns
+            .iter()
+            .filter(|c| c.index.is_none())
+            .map(|c| Violation {
+                line: 1,
+                column: 0,
+                name: c.schema.name.clone(),
+                message: "column is missing".to_string(),
+            })
+            .collect()
+    }
+}
+
+impl RowValidator for NativeValidator {
+    fn validate(&mut self, line: u64, record: &StringRecord) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        for column in self.columns.iter_mut() {
+            let Some(index) = column.index else {
+                continue;
+            };
+            let value = record.get(index).unwrap_or_default();
+            for message in column.check(value) {
+                violations.push(Violation {
+                    line,
+                    column: index + 1,
+                    name: column.schema.name.clone(),
+                    message,
+                });
+            }
+        }
+        violations
+    }
+}
+
+impl JsonSchemaValidator {
+    fn try_new(schema: Value, headers: &StringRecord) -> Result<Self> {
+        // CSV cells are always strings, so coerce them using the declared property types
+        let types = schema
+            .get("properties")
+            .and_then(|p| p.as_object())
+            .map(|props| {
+                props
+                    .iter()
+                    .filter_map(|(k, v)| {
+                        let ty = v.get("type")?.as_str()?;
+                        Some((k.clone(), ty.to_string()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let validator = jsonschema::validator_for(&schema)
+            .map_err(|e| anyhow!("Invalid JSON Schema: {}", e))?;
+        Ok(Self {
+            headers: headers.clone(),
+            types,
+            validator,
+        })
+    }
+
+    fn to_value(&self, record: &StringRecord) -> Value {
+        let mut map = Map::new();
+        for (header, cell) in self.headers.iter().zip(record.iter()) {
+            // empty cells are treated as absent so that `required` applies
+            if cell.is_empty() {
+                continue;
+            }
+            let value = match self.types.get(header).map(|t| t.as_str()) {
+                Some("integer") => cell.parse::<i64>().map(Value::from).ok(),
+                Some("number") => cell.parse::<f64>().map(Value::from).ok(),
+                Some("boolean") => cell.parse::<bool>().map(Value::from).ok(),
+                _ => None,
+            };
+            map.insert(
+                header.to_string(),
+                value.unwrap_or_else(|| Value::String(cell.to_string())),
+            );
+        }
+        Value::Object(map)
+    }
+}
+
+impl RowValidator for JsonSchemaValidator {
+    fn validate(&mut self, line: u64, record: &StringRecord) -> Vec<Violation> {
+        let instance = self.to_value(record);
+        let mut violations = self
+            .validator
+            .iter_errors(&instance)
+            .map(|e| {
+                let name = match &e.kind {
+                    ValidationErrorKind::Required { property } => {
+                        property.as_str().unwrap_or_default().to_string()
+                    }
+                    _ => {
+                        let path = e.instance_path.to_string();
+                        let name = path.trim_start_matches('/').replace("~1", "/");
+                        name.replace("~0", "~")
+                    }
+                };
+                let column = self
+                    .headers
+                    .iter()
+                    .position(|h| h == name)
+                    .map(|i| i + 1)
+                    .unwrap_or_default();
+                Violation {
+                    line,
+                    column,
+                    name,
+                    message: e.to_string(),
+                }
+            })
+            .collect::<Vec<_>>();
+        violations.sort_by_key(|v| v.column);
+        violations
+    }
+}
+
+/// Stream `input` and report every violation of `schema` through `on_violation`.
+///
+/// The schema file is either a native [`CsvSchema`] (it has a `columns` key) or a
+/// JSON Schema describing a single row. Returns the number of violations found.
+pub fn process_csv_validate(
+    input: &str,
+    schema: &str,
+    delimiter: u8,
+    mut on_violation: impl FnMut(&Violation),
+) -> Result<usize> {
+    let content = fs::read_to_string(schema)?;
+    let schema: Value = serde_yaml::from_str(&content)?;
+
+    // ragged rows are reported as violations rather than aborting the run
+    let mut reader = ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(crate::get_reader(input)?);
+    let headers = reader.headers()?.clone();
+
+    let mut count = 0;
+    let mut validator: Box<dyn RowValidator> = if schema.get("columns").is_some() {
+        let schema: CsvSchema = serde_json::from_value(schema)?;
+        let validator = NativeValidator::try_new(schema, &headers)?;
+        for v in validator.check_headers() {
+            on_violation(&v);
+            count += 1;
+        }
+        Box::new(validator)
+    } else {
+        Box::new(JsonSchemaValidator::try_new(schema, &headers)?)
+    };
+
+    let mut record = StringRecord::new();
+    while reader.read_record(&mut record)? {
+        let line = record.position().map(|p| p.line()).unwrap_or_default();
+        if record.len() != headers.len() {
+            on_violation(&Violation {
+                line,
+                column: 0,
+                name: "row".to_string(),
+                message: format!(
+                    "row has {} fields, the header has {}",
+                    record.len(),
+                    headers.len()
+                ),
+            });
+            count += 1;
+        }
+        for v in validator.validate(line, &record) {
+            on_violation(&v);
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_csv_validate_native() -> Result<()> {
+        let mut violations = Vec::new();
+        let count = process_csv_validate(
+            "assets/juventus.csv",
+            "fixtures/csv_validate/juventus.yaml",
+            b',',
+            |v| violations.push(v.to_string()),
+        )?;
+        assert_eq!(count, 0, "{:?}", violations);
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_validate_violations() -> Result<()> {
+        let mut violations = Vec::new();
+        process_csv_validate(
+            "fixtures/csv_validate/invalid.csv",
+            "fixtures/csv_validate/juventus.yaml",
+            b',',
+            |v| violations.push((v.line, v.column, v.name.clone())),
+        )?;
+        assert_eq!(
+            violations,
+            vec![
+                (2, 1, "Name".to_string()),
+                (3, 2, "Position".to_string()),
+                (3, 5, "Kit Number".to_string()),
+                (4, 1, "Name".to_string()),
+                (4, 5, "Kit Number".to_string()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_validate_ragged_rows() -> Result<()> {
+        let mut violations = Vec::new();
+        let count = process_csv_validate(
+            "fixtures/csv_validate/ragged.csv",
+            "fixtures/csv_validate/juventus.yaml",
+            b',',
+            |v| violations.push((v.line, v.column, v.name.clone())),
+        )?;
+        // the short row also misses its required kit number
+        assert_eq!(count, 3);
+        assert_eq!(
+            violations,
+            vec![
+                (2, 0, "row".to_string()),
+                (2, 5, "Kit Number".to_string()),
+                (3, 0, "row".to_string()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_validate_json_schema() -> Result<()> {
+        let mut violations = Vec::new();
+        process_csv_validate(
+            "fixtures/csv_validate/invalid.csv",
+            "fixtures/csv_validate/juventus.schema.json",
+            b',',
+            |v| violations.push((v.line, v.column)),
+        )?;
+        // JSON Schema has no notion of uniqueness across rows
+        assert_eq!(violations, vec![(2, 1), (3, 2), (3, 5)]);
+        Ok(())
+    }
+}
@@ -1,43 +1,667 @@
-use rand::seq::SliceRandom;
+use std::{fs, path::Path, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use rand::{seq::SliceRandom, Rng};
+use serde::Deserialize;
+use zxcvbn::zxcvbn;
+
+use super::BreachDb;
 
 const UPPER: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
 const LOWER: &[u8] = b"abcdefghijkmnopqrstuvwxyz";
 const NUMBER: &[u8] = b"123456789";
 const SYMBOL: &[u8] = b"!@#$%^&*()-_";
+// left out of the classes above by default because they are easily confused
+const AMBIGUOUS_UPPER: &[u8] = b"IO";
+const AMBIGUOUS_LOWER: &[u8] = b"l";
+const AMBIGUOUS_NUMBER: &[u8] = b"0";
+
+/// Built-in policies for `--policy`, see assets/policy.
+const POLICIES: &[(&str, &str)] = &[
+    ("alnum", include_str!("../../assets/policy/alnum.yaml")),
+    ("legacy", include_str!("../../assets/policy/legacy.yaml")),
+    ("pin", include_str!("../../assets/policy/pin.yaml")),
+    ("strict", include_str!("../../assets/policy/strict.yaml")),
+];
+
+/// Generation attempts before giving up on `max_repeat` or `min_score`.
+const MAX_ATTEMPTS: usize = 1000;
+
+/// Which characters `process_genpass` draws from and the rules the password
+/// must follow. A policy file is this struct in YAML, missing fields keep
+/// their defaults.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GenPassConfig {
+    pub length: usize,
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    pub upper: bool,
+    pub lower: bool,
+    pub number: bool,
+    pub symbol: bool,
+    /// include 0, O, l and I
+    pub ambiguous: bool,
+    /// custom alphabet, replaces the classes above
+    pub charset: Option<String>,
+    /// characters removed from every class
+    pub exclude: String,
+    /// symbols to draw from instead of the default set
+    pub symbols: Option<String>,
+    pub min_upper: usize,
+    pub min_lower: usize,
+    pub min_number: usize,
+    pub min_symbol: usize,
+    /// longest allowed run of the same character
+    pub max_repeat: Option<usize>,
+    pub start_with_letter: bool,
+    /// minimum zxcvbn score, from 0 to 4
+    pub min_score: u8,
+    /// reject passwords found in this breach dataset
+    #[serde(skip)]
+    pub breach_db: Option<Arc<BreachDb>>,
+}
+
+/// A character class and how many of its characters the password needs.
+#[derive(Debug)]
+pub struct CharClass {
+    pub name: &'static str,
+    pub chars: Vec<char>,
+    pub min: usize,
+}
+
+impl Default for GenPassConfig {
+    fn default() -> Self {
+        Self {
+            length: 16,
+            min_length: None,
+            max_length: None,
+            upper: true,
+            lower: true,
+            number: true,
+            symbol: true,
+            ambiguous: false,
+            charset: None,
+            exclude: String::new(),
+            symbols: None,
+            min_upper: 1,
+            min_lower: 1,
+            min_number: 1,
+            min_symbol: 1,
+            max_repeat: None,
+            start_with_letter: false,
+            min_score: 0,
+            breach_db: None,
+        }
+    }
+}
+
+impl GenPassConfig {
+    /// Load a policy from a YAML file, or one of the built-in policies by name.
+    pub fn from_policy(policy: &str) -> Result<Self> {
+        let content = if Path::new(policy).is_file() {
+            fs::read_to_string(policy)?
+        } else {
+            POLICIES
+                .iter()
+                .find(|(name, _)| *name == policy)
+                .map(|(_, content)| content.to_string())
+                .ok_or_else(|| {
+                    let names = POLICIES.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+                    anyhow!(
+                        "Unknown policy {}, expected a file or one of: {}",
+                        policy,
+                        names.join(", ")
+                    )
+                })?
+        };
+        serde_yaml::from_str(&content).map_err(|e| anyhow!("Invalid policy {}: {}", policy, e))
+    }
+
+    /// Character classes to draw from, with the number of characters each
+    /// class must contribute.
+    pub fn classes(&self) -> Result<Vec<CharClass>> {
+        let mut classes = Vec::new();
+        if let Some(charset) = &self.charset {
+            classes.push(CharClass {
+                name: "charset",
+                chars: self.filter(charset.chars(), "--charset")?,
+                min: 1,
+            });
+        } else {
+            let symbols = self
+                .symbols
+                .as_ref()
+                .map(|s| s.as_bytes())
+                .unwrap_or(SYMBOL);
+            let enabled = [
+                (
+                    self.upper,
+                    UPPER,
+                    AMBIGUOUS_UPPER,
+                    self.min_upper,
+                    "uppercase",
+                ),
+                (
+                    self.lower,
+                    LOWER,
+                    AMBIGUOUS_LOWER,
+                    self.min_lower,
+                    "lowercase",
+                ),
+                (
+                    self.number,
+                    NUMBER,
+                    AMBIGUOUS_NUMBER,
+                    self.min_number,
+                    "number",
+                ),
+                (self.symbol, symbols, &[][..], self.min_symbol, "symbol"),
+            ];
+            for (_, chars, ambiguous, min, name) in enabled.into_iter().filter(|c| c.0) {
+                let extra = if self.ambiguous { ambiguous } else { &[] };
+                let chars = chars.iter().chain(extra).map(|c| *c as char);
+                classes.push(CharClass {
+                    name,
+                    chars: self.filter(chars, name)?,
+                    min,
+                });
+            }
+        }
+        if classes.is_empty() {
+            return Err(anyhow!("At least one character class must be enabled"));
+        }
+        Ok(classes)
+    }
+
+    fn filter(&self, chars: impl Iterator<Item = char>, name: &str) -> Result<Vec<char>> {
+        let mut ret = chars
+            .filter(|c| !self.exclude.contains(*c))
+            .collect::<Vec<_>>();
+        ret.sort_unstable();
+        ret.dedup();
+        if ret.is_empty() {
+            return Err(anyhow!("No {} characters left after --exclude", name));
+        }
+        Ok(ret)
+    }
+
+    /// Approximate entropy in bits of the passwords this config generates,
+    /// treating every character as drawn from the whole alphabet.
+    pub fn entropy(&self) -> Result<f64> {
+        let alphabet = alphabet(&self.classes()?);
+        Ok(self.length as f64 * (alphabet.len() as f64).log2())
+    }
+
+    fn validate(&self, classes: &[CharClass]) -> Result<()> {
+        if self.length == 0 {
+            return Err(anyhow!("Password length must be greater than 0"));
+        }
+        if let Some(min) = self.min_length.filter(|min| self.length < *min) {
+            return Err(anyhow!("Password length must be at least {}", min));
+        }
+        if let Some(max) = self.max_length.filter(|max| self.length > *max) {
+            return Err(anyhow!("Password length must be at most {}", max));
+        }
+        let required = classes.iter().map(|c| c.min).sum::<usize>();
+        if self.length < required {
+            return Err(anyhow!(
+                "Password length must be at least {} to include every character class",
+                required
+            ));
+        }
+        if self.max_repeat == Some(0) {
+            return Err(anyhow!("max_repeat must be at least 1"));
+        }
+        if self.min_score > 4 {
+            return Err(anyhow!("min_score must be between 0 and 4"));
+        }
+        if self.start_with_letter
+            && !classes
+                .iter()
+                .any(|c| c.chars.iter().any(|c| c.is_alphabetic()))
+        {
+            return Err(anyhow!(
+                "start_with_letter requires letters in the alphabet"
+            ));
+        }
+        Ok(())
+    }
 
-pub fn process_genpass(
-    length: u8,
-    upper: bool,
-    lower: bool,
-    number: bool,
-    symbol: bool,
-) -> anyhow::Result<String> {
-    let mut rng = rand::thread_rng();
-    let mut password = Vec::new();
-    let mut chars = Vec::new();
-    if upper {
-        chars.extend_from_slice(UPPER);
-        password.push(*UPPER.choose(&mut rng).expect("UPPER won't be empty"));
-    }
-    if lower {
-        chars.extend_from_slice(LOWER);
-        password.push(*LOWER.choose(&mut rng).expect("LOWER won't be empty"));
-    }
-    if number {
-        chars.extend_from_slice(NUMBER);
-        password.push(*NUMBER.choose(&mut rng).expect("NUMBER won't be empty"));
-    }
-    if symbol {
-        chars.extend_from_slice(SYMBOL);
-        password.push(*SYMBOL.choose(&mut rng).expect("SYMBOL won't be empty"));
-    }
-    for _ in 0..(length - password.len() as u8) {
-        let c = chars
-            .choose(&mut rng)
-            .expect("chars won't be empty in this context");
-        password.push(*c);
-    }
-    password.shuffle(&mut rng);
-
-    Ok(String::from_utf8(password)?)
+    fn is_acceptable(&self, password: &[char]) -> Result<bool> {
+        if let Some(max) = self.max_repeat {
+            if password.chunk_by(|a, b| a == b).any(|run| run.len() > max) {
+                return Ok(false);
+            }
+        }
+        let password = password.iter().collect::<String>();
+        if self.min_score > 0 && zxcvbn(&password, &[])?.score() < self.min_score {
+            return Ok(false);
+        }
+        if let Some(db) = &self.breach_db {
+            return Ok(!db.is_breached(&password)?);
+        }
+        Ok(true)
+    }
+}
+
+fn alphabet(classes: &[CharClass]) -> Vec<char> {
+    let mut ret = classes
+        .iter()
+        .flat_map(|c| c.chars.iter().copied())
+        .collect::<Vec<_>>();
+    ret.sort_unstable();
+    ret.dedup();
+    ret
+}
+
+/// Draw the required characters of each class, fill up from all classes and
+/// shuffle. Returns `None` when no letter is available to start with.
+///
+/// `pick(n)` returns a uniform index below `n`. Derived passwords depend on the
+/// exact sequence of calls, so the order of draws here must not change.
+fn generate(
+    config: &GenPassConfig,
+    classes: &[CharClass],
+    alphabet: &[char],
+    pick: &mut impl FnMut(usize) -> Result<usize>,
+) -> Result<Option<Vec<char>>> {
+    let mut password = Vec::with_capacity(config.length);
+    for class in classes {
+        for _ in 0..class.min {
+            password.push(class.chars[pick(class.chars.len())?]);
+        }
+    }
+    for _ in password.len()..config.length {
+        password.push(alphabet[pick(alphabet.len())?]);
+    }
+    // Fisher-Yates, spelled out so the draws don't depend on the rand version
+    for i in (1..password.len()).rev() {
+        password.swap(i, pick(i + 1)?);
+    }
+    if config.start_with_letter {
+        // swapping keeps the class counts intact
+        let Some(idx) = password.iter().position(|c| c.is_alphabetic()) else {
+            return Ok(None);
+        };
+        password.swap(0, idx);
+    }
+    Ok(Some(password))
+}
+
+/// Generate a password for `config`, taking every random choice from `pick`.
+pub(super) fn generate_with(
+    config: &GenPassConfig,
+    mut pick: impl FnMut(usize) -> Result<usize>,
+) -> Result<String> {
+    let classes = config.classes()?;
+    config.validate(&classes)?;
+    let alphabet = alphabet(&classes);
+    for _ in 0..MAX_ATTEMPTS {
+        let Some(password) = generate(config, &classes, &alphabet, &mut pick)? else {
+            continue;
+        };
+        if config.is_acceptable(&password)? {
+            return Ok(password.into_iter().collect());
+        }
+    }
+    Err(anyhow!(
+        "Could not generate a password satisfying the policy in {} attempts",
+        MAX_ATTEMPTS
+    ))
+}
+
+pub fn process_genpass(config: &GenPassConfig) -> Result<String> {
+    let mut rng = crate::secure_rng();
+    generate_with(config, |n| Ok(rng.gen_range(0..n)))
+}
+
+/// Letters that are hard to mishear or misspell when read out, see `process_pronounceable`.
+const CONSONANTS: &[u8] = b"bdfghjkmnprstvz";
+const VOWELS: &[u8] = b"aeiou";
+
+/// Generate a password of consonant-vowel syllables that can be read out,
+/// returning it with a lower bound of its entropy in bits.
+///
+/// Uppercase, digits and symbols follow the class flags: `min_upper` letters
+/// are capitalized and `min_number` digits and `min_symbol` symbols are
+/// inserted between syllables. The entropy only counts the choice of letters,
+/// digits and symbols, not their positions, and is much lower than that of a
+/// random password of the same length.
+pub fn process_pronounceable(config: &GenPassConfig) -> Result<(String, f64)> {
+    if config.charset.is_some() {
+        return Err(anyhow!(
+            "Pronounceable passwords don't support a custom charset"
+        ));
+    }
+    if !config.upper && !config.lower {
+        return Err(anyhow!(
+            "Pronounceable passwords need uppercase or lowercase letters"
+        ));
+    }
+    let classes = config.classes()?;
+    let class = |name| classes.iter().find(|c| c.name == name);
+    let consonants = config.filter(CONSONANTS.iter().map(|c| *c as char), "consonant")?;
+    let vowels = config.filter(VOWELS.iter().map(|c| *c as char), "vowel")?;
+    let inserts = ["number", "symbol"]
+        .into_iter()
+        .filter_map(class)
+        .flat_map(|c| std::iter::repeat_n(&c.chars, c.min))
+        .collect::<Vec<_>>();
+    let upper = match (config.upper, config.lower) {
+        (true, true) => class("uppercase").map(|c| c.min).unwrap_or_default(),
+        (false, true) => 0,
+        _ => usize::MAX,
+    };
+    config.validate(&classes)?;
+    let letters = config.length.saturating_sub(inserts.len());
+    if letters == 0 {
+        return Err(anyhow!(
+            "Password length must be greater than {} to fit the digits and symbols",
+            inserts.len()
+        ));
+    }
+
+    let mut entropy = (letters / 2) as f64 * ((consonants.len() * vowels.len()) as f64).log2();
+    if letters % 2 == 1 {
+        entropy += (consonants.len() as f64).log2();
+    }
+    entropy += inserts
+        .iter()
+        .map(|chars| (chars.len() as f64).log2())
+        .sum::<f64>();
+
+    let mut rng = crate::secure_rng();
+    for _ in 0..MAX_ATTEMPTS {
+        let mut syllables = Vec::with_capacity(letters / 2 + 1);
+        for i in (0..letters).step_by(2) {
+            let mut syllable = vec![*consonants
+                .choose(&mut rng)
+                .expect("consonants won't be empty")];
+            if i + 1 < letters {
+                syllable.push(*vowels.choose(&mut rng).expect("vowels won't be empty"));
+            }
+            syllables.push(syllable);
+        }
+        let mut password = syllables.concat();
+        let mut positions = (0..password.len()).collect::<Vec<_>>();
+        positions.shuffle(&mut rng);
+        for i in positions.into_iter().take(upper) {
+            password[i] = password[i].to_ascii_uppercase();
+        }
+        // insert between syllables, keeping each syllable readable as a unit
+        let mut boundaries = (0..=syllables.len())
+            .map(|i| syllables[..i].iter().map(|s| s.len()).sum::<usize>())
+            .filter(|b| *b > 0 || !config.start_with_letter)
+            .collect::<Vec<_>>();
+        for chars in &inserts {
+            let at = *boundaries
+                .choose(&mut rng)
+                .expect("boundaries won't be empty");
+            password.insert(at, *chars.choose(&mut rng).expect("classes won't be empty"));
+            // later boundaries shift by the inserted character
+            boundaries
+                .iter_mut()
+                .filter(|b| **b > at)
+                .for_each(|b| *b += 1);
+        }
+        if config.is_acceptable(&password)? {
+            return Ok((password.into_iter().collect(), entropy));
+        }
+    }
+    Err(anyhow!(
+        "Could not generate a password satisfying the policy in {} attempts",
+        MAX_ATTEMPTS
+    ))
+}
+
+/// Default passphrase wordlist, see assets/README.md.
+const WORDLIST: &str = include_str!("../../assets/wordlist/eff_large_wordlist.txt");
+
+/// Parse a wordlist with one word per line. Lines in the EFF diceware format
+/// (`11111\tabacus`) are accepted too, the dice roll is ignored.
+fn parse_wordlist(content: &str) -> Vec<&str> {
+    let mut words = content
+        .lines()
+        .filter_map(|line| line.split_whitespace().last())
+        .collect::<Vec<_>>();
+    // duplicates would silently inflate the reported entropy
+    words.sort_unstable();
+    words.dedup();
+    words
+}
+
+/// Generate a passphrase of `count` words, returning it with its entropy in bits.
+pub fn process_passphrase(
+    count: usize,
+    wordlist: Option<&str>,
+    separator: &str,
+    capitalize: bool,
+    append_number: bool,
+    append_symbol: bool,
+) -> Result<(String, f64)> {
+    let words = parse_wordlist(wordlist.unwrap_or(WORDLIST));
+    if words.len() < 2 {
+        return Err(anyhow!("Wordlist must contain at least 2 words"));
+    }
+    if count == 0 {
+        return Err(anyhow!("Passphrase must contain at least 1 word"));
+    }
+    let mut rng = crate::secure_rng();
+    let mut phrase = (0..count)
+        .map(|_| {
+            let word = *words.choose(&mut rng).expect("words won't be empty");
+            if capitalize {
+                let mut chars = word.chars();
+                chars
+                    .next()
+                    .map(|c| c.to_uppercase().chain(chars).collect())
+                    .unwrap_or_default()
+            } else {
+                word.to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(separator);
+    let mut entropy = count as f64 * (words.len() as f64).log2();
+    if append_number {
+        phrase.push(*NUMBER.choose(&mut rng).expect("NUMBER won't be empty") as char);
+        entropy += (NUMBER.len() as f64).log2();
+    }
+    if append_symbol {
+        phrase.push(*SYMBOL.choose(&mut rng).expect("SYMBOL won't be empty") as char);
+        entropy += (SYMBOL.len() as f64).log2();
+    }
+    Ok((phrase, entropy))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_genpass() -> Result<()> {
+        let password = process_genpass(&GenPassConfig {
+            length: 300,
+            ..Default::default()
+        })?;
+        assert_eq!(password.chars().count(), 300);
+        assert!(password.chars().any(|c| c.is_ascii_uppercase()));
+        assert!(password.chars().any(|c| c.is_ascii_lowercase()));
+        assert!(password.chars().any(|c| c.is_ascii_digit()));
+        assert!(!password.contains(['0', 'O', 'l', 'I']));
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_genpass_charset() -> Result<()> {
+        let password = process_genpass(&GenPassConfig {
+            symbol: false,
+            number: false,
+            exclude: "abc".to_string(),
+            ..Default::default()
+        })?;
+        assert!(password.chars().all(|c| c.is_ascii_alphabetic()));
+        assert!(!password.contains(['a', 'b', 'c']));
+
+        let password = process_genpass(&GenPassConfig {
+            charset: Some("01".to_string()),
+            ..Default::default()
+        })?;
+        assert!(password.chars().all(|c| c == '0' || c == '1'));
+
+        let config = GenPassConfig {
+            ambiguous: true,
+            ..Default::default()
+        };
+        assert!(config.classes()?[2].chars.contains(&'0'));
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_genpass_invalid() {
+        let too_short = GenPassConfig {
+            length: 3,
+            ..Default::default()
+        };
+        assert!(process_genpass(&too_short).is_err());
+        let no_class = GenPassConfig {
+            upper: false,
+            lower: false,
+            number: false,
+            symbol: false,
+            ..Default::default()
+        };
+        assert!(process_genpass(&no_class).is_err());
+        let excluded = GenPassConfig {
+            exclude: "123456789".to_string(),
+            ..Default::default()
+        };
+        assert!(process_genpass(&excluded).is_err());
+    }
+
+    #[test]
+    fn test_process_genpass_policy() -> Result<()> {
+        for _ in 0..20 {
+            let config = GenPassConfig::from_policy("legacy")?;
+            let password = process_genpass(&config)?.chars().collect::<Vec<_>>();
+            assert!(password.len() <= 20);
+            assert!(password[0].is_ascii_alphabetic());
+            assert!(password.iter().filter(|c| c.is_ascii_digit()).count() >= 2);
+            assert!(password.chunk_by(|a, b| a == b).all(|run| run.len() <= 2));
+            assert!(password
+                .iter()
+                .all(|c| c.is_ascii_alphanumeric() || "!#$%-_".contains(*c)));
+        }
+
+        let pin = process_genpass(&GenPassConfig::from_policy("pin")?)?;
+        assert_eq!(pin.len(), 6);
+        assert!(pin.chars().all(|c| c.is_ascii_digit()));
+
+        let mut config = GenPassConfig::from_policy("legacy")?;
+        config.length = 21;
+        assert!(process_genpass(&config).is_err());
+        assert!(GenPassConfig::from_policy("unknown").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_genpass_min_score() -> Result<()> {
+        let config = GenPassConfig {
+            length: 12,
+            min_score: 4,
+            ..Default::default()
+        };
+        let password = process_genpass(&config)?;
+        assert_eq!(zxcvbn(&password, &[])?.score(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_genpass_breached() -> Result<()> {
+        let mut config = GenPassConfig {
+            length: 6,
+            charset: Some("1".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(process_genpass(&config)?, "111111");
+        config.breach_db = Some(Arc::new(BreachDb::open(
+            "fixtures/hibp/sha1.txt",
+            crate::BreachHash::Sha1,
+        )?));
+        assert!(process_genpass(&config).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_pronounceable() -> Result<()> {
+        let config = GenPassConfig {
+            length: 12,
+            upper: false,
+            symbol: false,
+            ..Default::default()
+        };
+        for _ in 0..20 {
+            let (password, entropy) = process_pronounceable(&config)?;
+            assert_eq!(password.len(), 12);
+            assert_eq!(password.chars().filter(|c| c.is_ascii_digit()).count(), 1);
+            let letters = password.replace(|c: char| c.is_ascii_digit(), "");
+            for (i, c) in letters.chars().enumerate() {
+                let set = if i % 2 == 0 { CONSONANTS } else { VOWELS };
+                assert!(set.contains(&(c as u8)), "{} in {}", c, password);
+            }
+            // 5 syllables, a trailing consonant and a digit
+            let expected = 5.0 * 75f64.log2() + 15f64.log2() + 9f64.log2();
+            assert!((entropy - expected).abs() < 1e-9);
+        }
+
+        let (password, _) = process_pronounceable(&GenPassConfig::default())?;
+        assert_eq!(password.len(), 16);
+        assert_eq!(
+            password.chars().filter(|c| c.is_ascii_uppercase()).count(),
+            1
+        );
+        assert!(process_pronounceable(&GenPassConfig {
+            length: 2,
+            ..Default::default()
+        })
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_genpass_entropy() -> Result<()> {
+        let config = GenPassConfig {
+            length: 10,
+            charset: Some("0123456789abcdef".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(config.entropy()?, 40.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_wordlist() {
+        assert_eq!(WORDLIST.lines().count(), 7776);
+        assert_eq!(parse_wordlist(WORDLIST).len(), 7776);
+        assert_eq!(WORDLIST.lines().next(), Some("11111\tabacus"));
+        assert_eq!(
+            parse_wordlist("11111\tabacus\n11112\tabdomen\n11113\tabacus\n"),
+            vec!["abacus", "abdomen"]
+        );
+    }
+
+    #[test]
+    fn test_process_passphrase() -> Result<()> {
+        let (phrase, entropy) = process_passphrase(6, None, "-", true, true, false)?;
+        let parts = phrase.split('-').collect::<Vec<_>>();
+        assert_eq!(parts.len(), 6);
+        assert!(parts
+            .iter()
+            .all(|w| w.starts_with(|c: char| c.is_ascii_uppercase())));
+        assert!(phrase.ends_with(|c: char| c.is_ascii_digit()));
+        assert!((entropy - (6.0 * 7776f64.log2() + 9f64.log2())).abs() < 1e-9);
+        assert!(process_passphrase(0, None, "-", false, false, false).is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,244 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Utc};
+use rand::{seq::SliceRandom, Rng};
+use ulid::Ulid;
+use uuid::{Builder, Uuid};
+
+use crate::IdKind;
+
+const NANOID_ALPHABET: &str = "_-0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const NANOID_LENGTH: usize = 21;
+const BYTES_LENGTH: usize = 16;
+const MAX_LENGTH: usize = 1024;
+/// Twitter's snowflake epoch, 2010-11-04T01:42:54.657Z.
+const SNOWFLAKE_EPOCH: u64 = 1288834974657;
+const SNOWFLAKE_MAX_WORKER: u16 = 0x3ff;
+const SNOWFLAKE_MAX_SEQUENCE: u64 = 0xfff;
+
+/// Twitter style snowflakes: 41 bits of milliseconds since `SNOWFLAKE_EPOCH`,
+/// a 10 bit worker id and a 12 bit sequence within the millisecond.
+struct Snowflake {
+    worker: u64,
+    last: u64,
+    sequence: u64,
+}
+
+impl Snowflake {
+    fn next(&mut self) -> Result<u64> {
+        let mut millis = now_millis()?.saturating_sub(SNOWFLAKE_EPOCH);
+        if millis <= self.last {
+            // same millisecond, or the clock went back: keep counting on `last`
+            self.sequence = (self.sequence + 1) & SNOWFLAKE_MAX_SEQUENCE;
+            if self.sequence == 0 {
+                while millis <= self.last {
+                    std::thread::yield_now();
+                    millis = now_millis()?.saturating_sub(SNOWFLAKE_EPOCH);
+                }
+                self.last = millis;
+            }
+        } else {
+            self.last = millis;
+            self.sequence = 0;
+        }
+        Ok(self.last << 22 | self.worker << 12 | self.sequence)
+    }
+}
+
+/// Generate `count` identifiers of `kind`.
+///
+/// `length` is the number of characters for nanoids and of random bytes for
+/// hex, base64 (URL safe, unpadded) and base58; `alphabet` only applies to
+/// nanoids. UUIDv7s and ULIDs generated in the same millisecond are not
+/// guaranteed to sort in generation order; snowflakes are, within one call,
+/// and embed `worker` (0 to 1023, default 0).
+pub fn process_id(
+    kind: IdKind,
+    count: usize,
+    length: Option<usize>,
+    alphabet: Option<&str>,
+    worker: Option<u16>,
+) -> Result<Vec<String>> {
+    let length = match (kind, length) {
+        (IdKind::Nanoid | IdKind::Hex | IdKind::Base64 | IdKind::Base58, Some(length)) => {
+            if !(1..=MAX_LENGTH).contains(&length) {
+                return Err(anyhow!("Length must be between 1 and {}", MAX_LENGTH));
+            }
+            length
+        }
+        (IdKind::Nanoid, None) => NANOID_LENGTH,
+        (_, None) => BYTES_LENGTH,
+        (_, Some(_)) => return Err(anyhow!("{} doesn't support a custom length", kind)),
+    };
+    let alphabet = match (kind, alphabet) {
+        (IdKind::Nanoid, alphabet) => nanoid_alphabet(alphabet.unwrap_or(NANOID_ALPHABET))?,
+        (_, None) => Vec::new(),
+        (_, Some(_)) => return Err(anyhow!("Only nanoid supports a custom alphabet")),
+    };
+    let worker = match (kind, worker) {
+        (IdKind::Snowflake, Some(worker)) if worker > SNOWFLAKE_MAX_WORKER => {
+            return Err(anyhow!(
+                "Worker id must be between 0 and {}",
+                SNOWFLAKE_MAX_WORKER
+            ))
+        }
+        (IdKind::Snowflake, worker) => worker.unwrap_or_default(),
+        (_, None) => 0,
+        (_, Some(_)) => return Err(anyhow!("Only snowflake supports a worker id")),
+    };
+    let mut snowflake = Snowflake {
+        worker: worker as u64,
+        last: 0,
+        sequence: 0,
+    };
+
+    let mut rng = crate::secure_rng();
+    let mut ret = Vec::with_capacity(count);
+    for _ in 0..count {
+        let id = match kind {
+            IdKind::Uuid4 => Builder::from_random_bytes(rng.gen())
+                .into_uuid()
+                .to_string(),
+            IdKind::Uuid7 => Builder::from_unix_timestamp_millis(now_millis()?, &rng.gen())
+                .into_uuid()
+                .to_string(),
+            IdKind::Ulid => Ulid::from_parts(now_millis()?, rng.gen()).to_string(),
+            IdKind::Snowflake => snowflake.next()?.to_string(),
+            IdKind::Nanoid => (0..length)
+                .map(|_| *alphabet.choose(&mut rng).expect("alphabet won't be empty"))
+                .collect(),
+            IdKind::Hex | IdKind::Base64 | IdKind::Base58 => {
+                let mut bytes = vec![0u8; length];
+                rng.fill(bytes.as_mut_slice());
+                match kind {
+                    IdKind::Hex => hex::encode(bytes),
+                    IdKind::Base64 => URL_SAFE_NO_PAD.encode(bytes),
+                    _ => bs58::encode(bytes).into_string(),
+                }
+            }
+        };
+        ret.push(id);
+    }
+    Ok(ret)
+}
+
+fn nanoid_alphabet(alphabet: &str) -> Result<Vec<char>> {
+    let chars = alphabet.chars().collect::<Vec<_>>();
+    if chars.len() < 2 {
+        return Err(anyhow!("Alphabet must have at least 2 characters"));
+    }
+    // a repeated character would be picked more often than the others
+    let mut sorted = chars.clone();
+    sorted.sort_unstable();
+    if let Some(w) = sorted.windows(2).find(|w| w[0] == w[1]) {
+        return Err(anyhow!("Alphabet contains {:?} more than once", w[0]));
+    }
+    Ok(chars)
+}
+
+fn now_millis() -> Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64)
+}
+
+/// The kind and embedded creation time of a UUIDv7, ULID or snowflake.
+pub fn process_id_timestamp(id: &str) -> Result<(IdKind, DateTime<Utc>)> {
+    let id = id.trim();
+    let (kind, millis) = if let Ok(ulid) = Ulid::from_string(id) {
+        (IdKind::Ulid, ulid.timestamp_ms())
+    } else if let Ok(snowflake) = id.parse::<u64>() {
+        (IdKind::Snowflake, (snowflake >> 22) + SNOWFLAKE_EPOCH)
+    } else {
+        let uuid =
+            Uuid::parse_str(id).map_err(|_| anyhow!("{} is not a UUID, ULID or snowflake", id))?;
+        if uuid.get_version_num() != 7 {
+            return Err(anyhow!(
+                "UUID version {} has no Unix timestamp, only version 7 does",
+                uuid.get_version_num()
+            ));
+        }
+        let bytes = uuid.as_bytes();
+        let mut millis = [0u8; 8];
+        millis[2..].copy_from_slice(&bytes[..6]);
+        (IdKind::Uuid7, u64::from_be_bytes(millis))
+    };
+    let time = DateTime::from_timestamp_millis(millis as i64)
+        .ok_or_else(|| anyhow!("Timestamp {} is out of range", millis))?;
+    Ok((kind, time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_id() -> Result<()> {
+        let ids = process_id(IdKind::Uuid4, 3, None, None, None)?;
+        assert_eq!(ids.len(), 3);
+        assert_eq!(Uuid::parse_str(&ids[0])?.get_version_num(), 4);
+        assert_ne!(ids[0], ids[1]);
+
+        let id = &process_id(IdKind::Nanoid, 1, Some(8), Some("ab"), None)?[0];
+        assert_eq!(id.len(), 8);
+        assert!(id.chars().all(|c| c == 'a' || c == 'b'));
+        assert_eq!(
+            process_id(IdKind::Nanoid, 1, None, None, None)?[0].len(),
+            21
+        );
+
+        assert_eq!(process_id(IdKind::Hex, 1, Some(4), None, None)?[0].len(), 8);
+        assert_eq!(
+            process_id(IdKind::Base64, 1, None, None, None)?[0].len(),
+            22
+        );
+        let id = &process_id(IdKind::Base58, 1, None, None, None)?[0];
+        assert_eq!(bs58::decode(id).into_vec()?.len(), 16);
+
+        assert!(process_id(IdKind::Uuid4, 1, Some(8), None, None).is_err());
+        assert!(process_id(IdKind::Hex, 1, None, Some("ab"), None).is_err());
+        assert!(process_id(IdKind::Nanoid, 1, None, Some("aab"), None).is_err());
+        assert!(process_id(IdKind::Hex, 1, Some(0), None, None).is_err());
+        assert!(process_id(IdKind::Hex, 1, None, None, Some(1)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_id_timestamp() -> Result<()> {
+        let (kind, time) = process_id_timestamp("01ARZ3NDEKTSV4RRFFQ69G5FAV")?;
+        assert!(matches!(kind, IdKind::Ulid));
+        assert_eq!(time.timestamp_millis(), 1469922850259);
+
+        let (kind, time) = process_id_timestamp("017f22e2-79b0-7cc3-98c4-dc0c0c07398f")?;
+        assert!(matches!(kind, IdKind::Uuid7));
+        assert_eq!(time.timestamp_millis(), 0x017f22e279b0);
+
+        let before = Utc::now().timestamp_millis();
+        for kind in [IdKind::Uuid7, IdKind::Ulid, IdKind::Snowflake] {
+            let id = &process_id(kind, 1, None, None, None)?[0];
+            assert!(process_id_timestamp(id)?.1.timestamp_millis() >= before);
+        }
+
+        // from the Twitter API docs, created at Tue Dec 31 19:26:16 +0000 2019
+        let (kind, time) = process_id_timestamp("1212092628029698048")?;
+        assert!(matches!(kind, IdKind::Snowflake));
+        assert_eq!(time.timestamp(), 1577820376);
+
+        assert!(process_id_timestamp("550e8400-e29b-41d4-a716-446655440000").is_err());
+        assert!(process_id_timestamp("not an id").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_id_snowflake() -> Result<()> {
+        let ids = process_id(IdKind::Snowflake, 5000, None, None, Some(327))?
+            .iter()
+            .map(|id| id.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        assert!(ids.iter().all(|id| (id >> 12) & 0x3ff == 327));
+        assert!(process_id(IdKind::Snowflake, 1, None, None, Some(1024)).is_err());
+        assert!(process_id(IdKind::Snowflake, 1, Some(8), None, None).is_err());
+        Ok(())
+    }
+}
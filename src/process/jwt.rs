@@ -20,7 +20,7 @@ pub fn process_jwt_decode(token: &str) -> Result<Claims> {
     validation.validate_aud = false;
     validation.validate_exp = true;
     let token_data = decode::<Claims>(
-        &token,
+        token,
         &DecodingKey::from_secret(SECRET.as_ref()),
         &validation,
     )?;
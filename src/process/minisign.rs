@@ -0,0 +1,230 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use blake2::{Blake2b512, Digest};
+
+const UNTRUSTED_COMMENT: &str = "untrusted comment: ";
+const TRUSTED_COMMENT: &str = "trusted comment: ";
+
+/// Signature algorithms of minisign/signify files, identified by a two byte tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigAlgorithm {
+    /// `Ed`: Ed25519 over the message itself, as signify and old minisign sign
+    Ed25519,
+    /// `ED`: Ed25519 over the BLAKE2b-512 hash of the message, minisign's default
+    Ed25519Hashed,
+    /// `B3`: BLAKE3 keyed hash with a shared key, only understood by acli
+    Blake3,
+}
+
+impl SigAlgorithm {
+    fn tag(self) -> &'static [u8; 2] {
+        match self {
+            SigAlgorithm::Ed25519 => b"Ed",
+            SigAlgorithm::Ed25519Hashed => b"ED",
+            SigAlgorithm::Blake3 => b"B3",
+        }
+    }
+
+    fn from_tag(tag: &[u8]) -> Result<Self> {
+        match tag {
+            b"Ed" => Ok(SigAlgorithm::Ed25519),
+            b"ED" => Ok(SigAlgorithm::Ed25519Hashed),
+            b"B3" => Ok(SigAlgorithm::Blake3),
+            _ => Err(anyhow!(
+                "Unsupported signature algorithm {:?}",
+                String::from_utf8_lossy(tag)
+            )),
+        }
+    }
+
+    fn signature_len(self) -> usize {
+        match self {
+            SigAlgorithm::Blake3 => 32,
+            _ => 64,
+        }
+    }
+}
+
+/// A detached signature file in the minisign format:
+///
+/// ```text
+/// untrusted comment: <free text>
+/// base64(<algorithm tag> <key id> <signature>)
+/// trusted comment: <free text>
+/// base64(<signature of signature + trusted comment>)
+/// ```
+///
+/// signify files lack the last two lines, so `trusted` is optional.
+#[derive(Debug)]
+pub struct SignatureFile {
+    pub untrusted_comment: String,
+    pub algorithm: SigAlgorithm,
+    pub key_id: [u8; 8],
+    pub signature: Vec<u8>,
+    /// The trusted comment and its global signature
+    pub trusted: Option<(String, Vec<u8>)>,
+}
+
+impl SignatureFile {
+    /// The data the global signature covers.
+    pub fn trusted_data(signature: &[u8], comment: &str) -> Vec<u8> {
+        [signature, comment.as_bytes()].concat()
+    }
+}
+
+fn comment<'a>(line: Option<&'a str>, prefix: &str) -> Result<&'a str> {
+    line.and_then(|l| l.strip_prefix(prefix))
+        .ok_or_else(|| anyhow!("Expected a line starting with {:?}", prefix))
+}
+
+fn decode_line(line: Option<&str>) -> Result<Vec<u8>> {
+    let line = line.ok_or_else(|| anyhow!("Signature file is truncated"))?;
+    Ok(STANDARD.decode(line.trim())?)
+}
+
+impl FromStr for SignatureFile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut lines = s.lines().map(|l| l.trim_end_matches('\r'));
+        let untrusted_comment = comment(lines.next(), UNTRUSTED_COMMENT)?.to_string();
+        let blob = decode_line(lines.next())?;
+        if blob.len() < 10 {
+            return Err(anyhow!("Signature is too short"));
+        }
+        let algorithm = SigAlgorithm::from_tag(&blob[..2])?;
+        if blob.len() != 10 + algorithm.signature_len() {
+            return Err(anyhow!("Signature has an invalid length"));
+        }
+        let trusted = match lines.next().filter(|l| !l.is_empty()) {
+            Some(line) => {
+                let comment = comment(Some(line), TRUSTED_COMMENT)?.to_string();
+                Some((comment, decode_line(lines.next())?))
+            }
+            None => None,
+        };
+        Ok(Self {
+            untrusted_comment,
+            algorithm,
+            key_id: blob[2..10].try_into()?,
+            signature: blob[10..].to_vec(),
+            trusted,
+        })
+    }
+}
+
+impl fmt::Display for SignatureFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let blob = [
+            self.algorithm.tag().as_slice(),
+            &self.key_id,
+            &self.signature,
+        ]
+        .concat();
+        writeln!(f, "{}{}", UNTRUSTED_COMMENT, self.untrusted_comment)?;
+        writeln!(f, "{}", STANDARD.encode(blob))?;
+        if let Some((comment, signature)) = &self.trusted {
+            writeln!(f, "{}{}", TRUSTED_COMMENT, comment)?;
+            writeln!(f, "{}", STANDARD.encode(signature))?;
+        }
+        Ok(())
+    }
+}
+
+/// An Ed25519 public key in the minisign/signify format.
+#[derive(Debug)]
+pub struct PublicKeyFile {
+    pub key_id: [u8; 8],
+    pub key: [u8; 32],
+}
+
+impl PublicKeyFile {
+    /// A public key without an ID of its own gets one derived from the key,
+    /// so signing and verifying with the raw key files agree on it.
+    pub fn from_raw(key: [u8; 32]) -> Self {
+        let hash = Blake2b512::digest(key);
+        let key_id = hash[..8].try_into().expect("hash is 64 bytes");
+        Self { key_id, key }
+    }
+
+    /// Read a minisign public key file, with or without its untrusted comment line.
+    pub fn parse(key: &str) -> Result<Self> {
+        let line = key
+            .lines()
+            .find(|l| !l.starts_with(UNTRUSTED_COMMENT) && !l.trim().is_empty());
+        let blob = decode_line(line)?;
+        if blob.len() != 42 || &blob[..2] != b"Ed" {
+            return Err(anyhow!("Invalid minisign public key"));
+        }
+        Ok(Self {
+            key_id: blob[2..10].try_into()?,
+            key: blob[10..].try_into()?,
+        })
+    }
+}
+
+impl fmt::Display for PublicKeyFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}minisign public key {}",
+            UNTRUSTED_COMMENT,
+            key_id_hex(&self.key_id)
+        )?;
+        let blob = [b"Ed".as_slice(), &self.key_id, &self.key].concat();
+        writeln!(f, "{}", STANDARD.encode(blob))
+    }
+}
+
+/// Key IDs as minisign prints them, a little endian u64 in hex.
+pub fn key_id_hex(key_id: &[u8; 8]) -> String {
+    format!("{:016X}", u64::from_le_bytes(*key_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUBLIC_KEY: &str = include_str!("../../fixtures/minisign/minisign.pub");
+
+    #[test]
+    fn test_public_key_file() -> Result<()> {
+        let key = PublicKeyFile::parse(PUBLIC_KEY)?;
+        assert_eq!(key_id_hex(&key.key_id), "B8985BEC989D99C1");
+        assert_eq!(key.to_string(), PUBLIC_KEY);
+        let bare = PUBLIC_KEY.lines().nth(1).expect("two lines");
+        assert_eq!(PublicKeyFile::parse(bare)?.key, key.key);
+
+        let raw = PublicKeyFile::from_raw(*include_bytes!("../../fixtures/minisign/ed25519.pk"));
+        assert_eq!(raw.key_id, key.key_id);
+        assert!(PublicKeyFile::parse("short").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_signature_file() -> Result<()> {
+        let content = include_str!("../../fixtures/minisign/message.txt.sig");
+        let sig: SignatureFile = content.parse()?;
+        assert_eq!(sig.algorithm, SigAlgorithm::Ed25519Hashed);
+        assert_eq!(key_id_hex(&sig.key_id), "B8985BEC989D99C1");
+        assert_eq!(
+            sig.trusted.as_ref().map(|t| t.0.as_str()),
+            Some("timestamp:1700000000\tfile:message.txt\thashed")
+        );
+        assert_eq!(sig.to_string(), content);
+
+        // signify files have no trusted comment
+        let sig: SignatureFile =
+            include_str!("../../fixtures/minisign/message.txt.signify.sig").parse()?;
+        assert_eq!(sig.algorithm, SigAlgorithm::Ed25519);
+        assert!(sig.trusted.is_none());
+
+        assert!("untrusted comment: x\nAAAA\n"
+            .parse::<SignatureFile>()
+            .is_err());
+        assert!("no comment\n".parse::<SignatureFile>().is_err());
+        Ok(())
+    }
+}
@@ -1,16 +1,62 @@
 mod b64;
+mod breached;
+mod codegen;
 mod csv_convert;
+mod csv_diff;
+mod csv_mask;
+mod csv_script;
+mod csv_split;
+mod csv_sqlite;
+mod csv_validate;
+mod derive;
 mod gen_pass;
 mod http_serve;
+mod id;
 mod jwt;
+mod key_format;
+mod minisign;
+mod otp;
+mod pass_check;
+mod pattern;
+mod recipient;
+mod secrets;
 mod text;
+mod xlsx;
 
 pub use b64::{process_decode, process_encode};
+pub use breached::{process_breached, BreachDb, BreachReport};
+pub use codegen::process_codegen;
 pub use csv_convert::process_csv;
-pub use gen_pass::process_genpass;
+pub use csv_diff::{process_csv_diff, CsvDiff};
+pub use csv_mask::process_csv_mask;
+pub use csv_script::{process_csv_map, RecordScript};
+pub use csv_split::process_csv_split;
+pub use csv_sqlite::{process_csv_to_sqlite, process_sqlite_export, sqlite_table_query};
+pub use csv_validate::{process_csv_validate, Violation};
+pub use derive::process_genpass_derive;
+pub use gen_pass::{
+    process_genpass, process_passphrase, process_pronounceable, CharClass, GenPassConfig,
+};
 pub use http_serve::process_http_serve;
+pub use id::{process_id, process_id_timestamp};
 pub use jwt::{process_jwt_decode, process_jwt_encode};
+pub use key_format::{
+    encode_public_key, encode_signing_key, is_encrypted_key, load_encrypted_signing_key,
+    load_public_key, load_signing_key, process_key_convert,
+};
+pub use minisign::{key_id_hex, PublicKeyFile, SigAlgorithm, SignatureFile};
+pub use otp::{decode_secret, process_qr_render, Otp};
+pub use pass_check::{process_pass_check, CrackTime, PasswordReport};
+pub use pattern::Pattern;
+pub use recipient::{
+    is_age_encrypted, parse_recipients, process_text_decrypt_identity,
+    process_text_encrypt_recipients, x25519_generate,
+};
+pub use secrets::{process_secrets_render, secret_names, Secret};
+pub(crate) use text::Blake3;
 pub use text::{
-    process_text_decrypt, process_text_encrypt, process_text_key_generate, process_text_sign,
-    process_text_verify,
+    is_password_encrypted, process_text_decrypt, process_text_decrypt_password,
+    process_text_encrypt, process_text_encrypt_password, process_text_key_generate,
+    process_text_sign, process_text_sign_detached, process_text_verify,
+    process_text_verify_detached, process_text_verify_raw,
 };
@@ -0,0 +1,306 @@
+use anyhow::{anyhow, Result};
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use qrcode::{render::unicode::Dense1x2, QrCode};
+use rand::RngCore;
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use url::Url;
+
+use crate::OtpAlgorithm;
+
+/// A TOTP (RFC 6238) or HOTP (RFC 4226) token, as found in `otpauth://` URIs.
+#[derive(Debug, Clone)]
+pub struct Otp {
+    pub secret: Vec<u8>,
+    pub algorithm: OtpAlgorithm,
+    pub digits: u32,
+    /// TOTP step in seconds, `None` for HOTP
+    pub period: Option<u64>,
+    /// HOTP counter
+    pub counter: u64,
+    pub issuer: Option<String>,
+    pub account: Option<String>,
+}
+
+impl Otp {
+    pub fn totp(secret: Vec<u8>) -> Self {
+        Self {
+            secret,
+            algorithm: OtpAlgorithm::Sha1,
+            digits: 6,
+            period: Some(30),
+            counter: 0,
+            issuer: None,
+            account: None,
+        }
+    }
+
+    pub fn hotp(secret: Vec<u8>, counter: u64) -> Self {
+        Self {
+            period: None,
+            counter,
+            ..Self::totp(secret)
+        }
+    }
+
+    /// A new token with a random 160 bit secret, the size RFC 4226 recommends.
+    pub fn generate(period: Option<u64>) -> Self {
+        let mut secret = vec![0u8; 20];
+        crate::secure_rng().fill_bytes(&mut secret);
+        Self {
+            period,
+            ..Self::totp(secret)
+        }
+    }
+
+    pub fn from_uri(uri: &str) -> Result<Self> {
+        let url = Url::parse(uri)?;
+        if url.scheme() != "otpauth" {
+            return Err(anyhow!("Expected an otpauth:// URI"));
+        }
+        let mut otp = match url.host_str() {
+            Some("totp") => Self::totp(Vec::new()),
+            Some("hotp") => Self::hotp(Vec::new(), 0),
+            _ => return Err(anyhow!("Expected otpauth://totp/ or otpauth://hotp/")),
+        };
+        // the label is `issuer:account` or just `account`
+        let label = percent_decode(url.path().trim_start_matches('/'))?;
+        match label.split_once(':') {
+            Some((issuer, account)) => {
+                otp.issuer = Some(issuer.to_string());
+                otp.account = Some(account.trim().to_string());
+            }
+            None if !label.is_empty() => otp.account = Some(label),
+            None => {}
+        }
+        let mut secret = None;
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "secret" => secret = Some(decode_secret(&value)?),
+                "issuer" => otp.issuer = Some(value.to_string()),
+                "algorithm" => otp.algorithm = value.to_lowercase().parse()?,
+                "digits" => otp.digits = value.parse()?,
+                "period" if otp.period.is_some() => otp.period = Some(value.parse()?),
+                "counter" => otp.counter = value.parse()?,
+                _ => {}
+            }
+        }
+        otp.secret = secret.ok_or_else(|| anyhow!("otpauth URI has no secret"))?;
+        otp.validate()?;
+        Ok(otp)
+    }
+
+    pub fn to_uri(&self) -> String {
+        let kind = if self.period.is_some() {
+            "totp"
+        } else {
+            "hotp"
+        };
+        let account = self.account.as_deref().unwrap_or_default();
+        let label = match &self.issuer {
+            Some(issuer) => format!("{}:{}", issuer, account),
+            None => account.to_string(),
+        };
+        let mut url = Url::parse(&format!("otpauth://{}/", kind)).expect("valid base URI");
+        url.set_path(&label);
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("secret", &BASE32_NOPAD.encode(&self.secret));
+            if let Some(issuer) = &self.issuer {
+                query.append_pair("issuer", issuer);
+            }
+            query
+                .append_pair("algorithm", &self.algorithm.to_string().to_uppercase())
+                .append_pair("digits", &self.digits.to_string());
+            match self.period {
+                Some(period) => query.append_pair("period", &period.to_string()),
+                None => query.append_pair("counter", &self.counter.to_string()),
+            };
+        }
+        url.to_string()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.secret.is_empty() {
+            return Err(anyhow!("OTP secret must not be empty"));
+        }
+        if !(6..=10).contains(&self.digits) {
+            return Err(anyhow!("OTP digits must be between 6 and 10"));
+        }
+        if self.period == Some(0) {
+            return Err(anyhow!("TOTP period must be greater than 0"));
+        }
+        Ok(())
+    }
+
+    /// The HOTP code for `counter`, RFC 4226 section 5.3.
+    pub fn code_at(&self, counter: u64) -> Result<String> {
+        let msg = counter.to_be_bytes();
+        let hash = match self.algorithm {
+            OtpAlgorithm::Sha1 => hmac::<Hmac<Sha1>>(&self.secret, &msg)?,
+            OtpAlgorithm::Sha256 => hmac::<Hmac<Sha256>>(&self.secret, &msg)?,
+            OtpAlgorithm::Sha512 => hmac::<Hmac<Sha512>>(&self.secret, &msg)?,
+        };
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes(hash[offset..offset + 4].try_into()?) & 0x7fff_ffff;
+        let code = binary as u64 % 10u64.pow(self.digits);
+        Ok(format!("{:0width$}", code, width = self.digits as usize))
+    }
+
+    /// The counter in effect at `time`: the time step for TOTP, the counter for HOTP.
+    pub fn counter_at(&self, time: u64) -> u64 {
+        match self.period {
+            Some(period) => time / period,
+            None => self.counter,
+        }
+    }
+
+    /// Seconds until the TOTP code at `time` expires.
+    pub fn remaining(&self, time: u64) -> Option<u64> {
+        self.period.map(|period| period - time % period)
+    }
+
+    /// Check `code` against the counters within `window` steps of the one in
+    /// effect at `time`. HOTP only looks ahead, as its counter never goes back.
+    /// Returns the offset of the matching step.
+    pub fn verify(&self, code: &str, time: u64, window: u64) -> Result<Option<i64>> {
+        let current = self.counter_at(time);
+        let behind = if self.period.is_some() { window } else { 0 };
+        for counter in current.saturating_sub(behind)..=current.saturating_add(window) {
+            if constant_time_eq(self.code_at(counter)?.as_bytes(), code.as_bytes()) {
+                return Ok(Some(counter as i64 - current as i64));
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn hmac<M: Mac + hmac::digest::KeyInit>(key: &[u8], msg: &[u8]) -> Result<Vec<u8>> {
+    let mut mac = <M as Mac>::new_from_slice(key).map_err(|e| anyhow!("{}", e))?;
+    mac.update(msg);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn percent_decode(s: &str) -> Result<String> {
+    Ok(percent_encoding::percent_decode_str(s)
+        .decode_utf8()?
+        .into_owned())
+}
+
+/// Decode a base32 secret, ignoring case, spaces and padding as authenticator apps do.
+pub fn decode_secret(secret: &str) -> Result<Vec<u8>> {
+    let secret = secret
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '=' && *c != '-')
+        .collect::<String>()
+        .to_uppercase();
+    BASE32_NOPAD
+        .decode(secret.as_bytes())
+        .map_err(|e| anyhow!("Invalid base32 secret: {}", e))
+}
+
+/// Render `data` as a QR code with unicode half blocks for the terminal.
+pub fn process_qr_render(data: &str) -> Result<String> {
+    let code = QrCode::new(data.as_bytes())?;
+    Ok(code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_hotp_rfc4226_vectors() -> Result<()> {
+        let expected = [
+            "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583",
+            "399871", "520489",
+        ];
+        let otp = Otp::hotp(RFC_SECRET.to_vec(), 0);
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(otp.code_at(counter as u64)?, *code);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_totp_rfc6238_vectors() -> Result<()> {
+        let secrets = [
+            (OtpAlgorithm::Sha1, RFC_SECRET.to_vec()),
+            (
+                OtpAlgorithm::Sha256,
+                b"12345678901234567890123456789012".to_vec(),
+            ),
+            (OtpAlgorithm::Sha512, RFC_SECRET.repeat(4)[..64].to_vec()),
+        ];
+        let expected = [
+            (59, ["94287082", "46119246", "90693936"]),
+            (1111111109, ["07081804", "68084774", "25091201"]),
+            (2000000000, ["69279037", "90698825", "38618901"]),
+        ];
+        for (time, codes) in expected {
+            for ((algorithm, secret), code) in secrets.iter().zip(codes) {
+                let otp = Otp {
+                    algorithm: *algorithm,
+                    digits: 8,
+                    ..Otp::totp(secret.clone())
+                };
+                assert_eq!(otp.code_at(otp.counter_at(time))?, code);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_otp_verify() -> Result<()> {
+        let otp = Otp::totp(RFC_SECRET.to_vec());
+        let code = otp.code_at(otp.counter_at(1000))?;
+        assert_eq!(otp.verify(&code, 1000, 1)?, Some(0));
+        assert_eq!(otp.verify(&code, 1030, 1)?, Some(-1));
+        assert_eq!(otp.verify(&code, 1090, 1)?, None);
+        assert_eq!(otp.remaining(1000), Some(20));
+
+        let hotp = Otp::hotp(RFC_SECRET.to_vec(), 3);
+        assert_eq!(hotp.verify("338314", 0, 2)?, Some(1));
+        assert_eq!(hotp.verify("287082", 0, 2)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_otp_uri() -> Result<()> {
+        let uri = "otpauth://totp/ACME%20Co:john.doe@email.com?secret=HXDMVJECJJWSRB3HWIZR4IFUGFTMXBOZ&issuer=ACME%20Co&algorithm=SHA256&digits=8&period=60";
+        let otp = Otp::from_uri(uri)?;
+        assert_eq!(otp.issuer.as_deref(), Some("ACME Co"));
+        assert_eq!(otp.account.as_deref(), Some("john.doe@email.com"));
+        assert_eq!(otp.digits, 8);
+        assert_eq!(otp.period, Some(60));
+        assert!(matches!(otp.algorithm, OtpAlgorithm::Sha256));
+
+        let round_trip = Otp::from_uri(&otp.to_uri())?;
+        assert_eq!(round_trip.secret, otp.secret);
+        assert_eq!(round_trip.issuer, otp.issuer);
+        assert_eq!(round_trip.account, otp.account);
+        assert_eq!(round_trip.period, Some(60));
+
+        assert!(Otp::from_uri("https://example.com").is_err());
+        assert!(Otp::from_uri("otpauth://totp/x?digits=6").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_secret() -> Result<()> {
+        assert_eq!(decode_secret("gezd gnbv gy3t qojq")?, b"1234567890");
+        assert!(decode_secret("not base32!").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use std::{
+    fmt,
+    io::{BufRead, BufReader},
+};
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use zxcvbn::{time_estimates::CrackTimeSeconds, zxcvbn};
+
+/// zxcvbn's estimate for one password. The password itself is left out so
+/// reports can be shared or logged.
+#[derive(Debug, Serialize)]
+pub struct PasswordReport {
+    pub line: usize,
+    pub score: u8,
+    pub guesses: u64,
+    pub guesses_log10: f64,
+    pub crack_times: Vec<CrackTime>,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CrackTime {
+    pub scenario: &'static str,
+    pub seconds: f64,
+    pub display: String,
+}
+
+impl CrackTime {
+    fn new(scenario: &'static str, time: CrackTimeSeconds) -> Self {
+        let seconds = match time {
+            CrackTimeSeconds::Integer(i) => i as f64,
+            CrackTimeSeconds::Float(f) => f,
+        };
+        Self {
+            scenario,
+            seconds,
+            display: time.to_string(),
+        }
+    }
+}
+
+impl PasswordReport {
+    pub fn try_new(line: usize, password: &str, user_inputs: &[&str]) -> Result<Self> {
+        let estimate =
+            zxcvbn(password, user_inputs).map_err(|e| anyhow!("line {}: {}", line, e))?;
+        let times = estimate.crack_times();
+        let feedback = estimate.feedback().as_ref();
+        Ok(Self {
+            line,
+            score: estimate.score(),
+            guesses: estimate.guesses(),
+            guesses_log10: estimate.guesses_log10(),
+            crack_times: vec![
+                CrackTime::new(
+                    "online, throttled (100/hour)",
+                    times.online_throttling_100_per_hour(),
+                ),
+                CrackTime::new(
+                    "online, unthrottled (10/second)",
+                    times.online_no_throttling_10_per_second(),
+                ),
+                CrackTime::new(
+                    "offline, slow hash (1e4/second)",
+                    times.offline_slow_hashing_1e4_per_second(),
+                ),
+                CrackTime::new(
+                    "offline, fast hash (1e10/second)",
+                    times.offline_fast_hashing_1e10_per_second(),
+                ),
+            ],
+            warning: feedback.and_then(|f| f.warning()).map(|w| w.to_string()),
+            suggestions: feedback
+                .map(|f| f.suggestions().iter().map(|s| s.to_string()).collect())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+impl fmt::Display for PasswordReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "line {}: score {}/4, 10^{:.1} guesses",
+            self.line, self.score, self.guesses_log10
+        )?;
+        for time in &self.crack_times {
+            writeln!(f, "  {:<34}{}", time.scenario, time.display)?;
+        }
+        if let Some(warning) = &self.warning {
+            writeln!(f, "  warning: {}", warning)?;
+        }
+        for suggestion in &self.suggestions {
+            writeln!(f, "  suggestion: {}", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// Estimate the strength of each password in `input`, one per line.
+///
+/// `user_inputs` are words an attacker is likely to try for this user, such as
+/// their name or company, and lower the score of passwords built from them.
+/// Blank lines are skipped.
+pub fn process_pass_check(input: &str, user_inputs: &[String]) -> Result<Vec<PasswordReport>> {
+    let user_inputs = user_inputs.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+    let reader = BufReader::new(crate::get_reader(input)?);
+    let mut ret = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let password = line.trim_end_matches('\r');
+        if password.is_empty() {
+            continue;
+        }
+        ret.push(PasswordReport::try_new(i + 1, password, &user_inputs)?);
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_password_report() -> Result<()> {
+        let weak = PasswordReport::try_new(1, "password1", &[])?;
+        assert_eq!(weak.score, 0);
+        assert!(weak.warning.is_some());
+        assert_eq!(weak.crack_times.len(), 4);
+
+        let strong = PasswordReport::try_new(2, "q8x-yZZMEsQNc8f2uF4iRtvE", &[])?;
+        assert_eq!(strong.score, 4);
+        assert!(strong.guesses > weak.guesses);
+        Ok(())
+    }
+
+    #[test]
+    fn test_password_report_user_inputs() -> Result<()> {
+        let without = PasswordReport::try_new(1, "juventus1897", &[])?;
+        let with = PasswordReport::try_new(1, "juventus1897", &["juventus"])?;
+        assert!(with.guesses <= without.guesses);
+        Ok(())
+    }
+}
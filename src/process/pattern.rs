@@ -0,0 +1,206 @@
+use anyhow::{anyhow, Result};
+use rand::{seq::SliceRandom, Rng};
+use regex_syntax::hir::{Class, Hir, HirKind};
+
+/// Extra repetitions allowed for the unbounded `*`, `+` and `{n,}` quantifiers.
+const MAX_EXTRA_REPEAT: u32 = 8;
+
+/// A parsed `--pattern`, either a template or a `/regex/`.
+///
+/// Templates use one character per generated character:
+///
+/// | char | generates              |
+/// |------|------------------------|
+/// | `X`  | uppercase letter       |
+/// | `x`  | lowercase letter       |
+/// | `9`  | digit                  |
+/// | `A`  | letter                 |
+/// | `*`  | letter or digit        |
+/// | `!`  | symbol                 |
+/// | `\c` | the literal `c`        |
+///
+/// Any other character is copied as is, so `XXXX-9999-xxxx` generates e.g.
+/// `KQZD-4821-mxpe`. Regexes support literals, classes, groups, alternation
+/// and quantifiers; classes are limited to printable ASCII and unbounded
+/// quantifiers to `MAX_EXTRA_REPEAT` extra repetitions. Anchors are ignored.
+#[derive(Debug)]
+pub struct Pattern(Node);
+
+#[derive(Debug)]
+enum Node {
+    Literal(String),
+    Class(Vec<char>),
+    Concat(Vec<Node>),
+    Alternation(Vec<Node>),
+    Repeat { node: Box<Node>, min: u32, max: u32 },
+}
+
+fn ascii_class(chars: &str) -> Node {
+    Node::Class(chars.chars().collect())
+}
+
+impl Pattern {
+    pub fn parse(pattern: &str) -> Result<Self> {
+        let node = match pattern.strip_prefix('/').and_then(|p| p.strip_suffix('/')) {
+            Some(regex) => Node::from_regex(regex)?,
+            None => Node::from_template(pattern)?,
+        };
+        Ok(Self(node))
+    }
+
+    pub fn generate(&self) -> String {
+        let mut rng = crate::secure_rng();
+        let mut ret = String::new();
+        self.0.generate(&mut rng, &mut ret);
+        ret
+    }
+
+    /// Lower bound of the entropy in bits of the generated strings.
+    pub fn entropy(&self) -> f64 {
+        self.0.entropy()
+    }
+}
+
+impl Node {
+    fn from_template(template: &str) -> Result<Self> {
+        let mut nodes = Vec::new();
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            let node = match c {
+                'X' => ascii_class("ABCDEFGHIJKLMNOPQRSTUVWXYZ"),
+                'x' => ascii_class("abcdefghijklmnopqrstuvwxyz"),
+                '9' => ascii_class("0123456789"),
+                'A' => ascii_class("ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz"),
+                '*' => {
+                    ascii_class("ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789")
+                }
+                '!' => ascii_class("!@#$%^&*()-_"),
+                '\\' => {
+                    let c = chars
+                        .next()
+                        .ok_or_else(|| anyhow!("Pattern ends with an unfinished escape"))?;
+                    Node::Literal(c.to_string())
+                }
+                c => Node::Literal(c.to_string()),
+            };
+            nodes.push(node);
+        }
+        if nodes.is_empty() {
+            return Err(anyhow!("Pattern must not be empty"));
+        }
+        Ok(Node::Concat(nodes))
+    }
+
+    fn from_regex(regex: &str) -> Result<Self> {
+        let hir = regex_syntax::parse(regex).map_err(|e| anyhow!("Invalid regex: {}", e))?;
+        Self::from_hir(&hir)
+    }
+
+    fn from_hir(hir: &Hir) -> Result<Self> {
+        let node = match hir.kind() {
+            HirKind::Empty | HirKind::Look(_) => Node::Literal(String::new()),
+            HirKind::Literal(lit) => Node::Literal(String::from_utf8(lit.0.to_vec())?),
+            HirKind::Class(Class::Unicode(class)) => {
+                let chars = class
+                    .iter()
+                    .flat_map(|r| r.start().max(' ')..=r.end().min('~'))
+                    .collect::<Vec<_>>();
+                if chars.is_empty() {
+                    return Err(anyhow!("Regex class has no printable ASCII characters"));
+                }
+                Node::Class(chars)
+            }
+            HirKind::Class(Class::Bytes(_)) => {
+                return Err(anyhow!("Byte classes are not supported in patterns"))
+            }
+            HirKind::Repetition(rep) => Node::Repeat {
+                node: Box::new(Self::from_hir(&rep.sub)?),
+                min: rep.min,
+                max: rep.max.unwrap_or(rep.min + MAX_EXTRA_REPEAT),
+            },
+            HirKind::Capture(cap) => Self::from_hir(&cap.sub)?,
+            HirKind::Concat(subs) => {
+                Node::Concat(subs.iter().map(Self::from_hir).collect::<Result<_>>()?)
+            }
+            HirKind::Alternation(subs) => {
+                Node::Alternation(subs.iter().map(Self::from_hir).collect::<Result<_>>()?)
+            }
+        };
+        Ok(node)
+    }
+
+    fn generate(&self, rng: &mut impl Rng, out: &mut String) {
+        match self {
+            Node::Literal(s) => out.push_str(s),
+            Node::Class(chars) => out.push(*chars.choose(rng).expect("classes won't be empty")),
+            Node::Concat(nodes) => nodes.iter().for_each(|n| n.generate(rng, out)),
+            Node::Alternation(nodes) => nodes
+                .choose(rng)
+                .expect("alternations won't be empty")
+                .generate(rng, out),
+            Node::Repeat { node, min, max } => {
+                for _ in 0..rng.gen_range(*min..=*max) {
+                    node.generate(rng, out);
+                }
+            }
+        }
+    }
+
+    fn entropy(&self) -> f64 {
+        match self {
+            Node::Literal(_) => 0.0,
+            Node::Class(chars) => (chars.len() as f64).log2(),
+            Node::Concat(nodes) => nodes.iter().map(Node::entropy).sum(),
+            Node::Alternation(nodes) => {
+                (nodes.len() as f64).log2()
+                    + nodes
+                        .iter()
+                        .map(Node::entropy)
+                        .fold(f64::INFINITY, f64::min)
+            }
+            Node::Repeat { node, min, max } => {
+                *min as f64 * node.entropy() + ((max - min + 1) as f64).log2()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    #[test]
+    fn test_pattern_template() -> Result<()> {
+        let pattern = Pattern::parse("XXXX-9999-xxxx")?;
+        let re = Regex::new(r"^[A-Z]{4}-[0-9]{4}-[a-z]{4}$")?;
+        for _ in 0..20 {
+            assert!(re.is_match(&pattern.generate()));
+        }
+        assert_eq!(Pattern::parse(r"\X9")?.generate().len(), 2);
+        assert!(Pattern::parse(r"\X9")?.generate().starts_with('X'));
+        assert!(Pattern::parse("ab\\").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pattern_regex() -> Result<()> {
+        let regex = r"[A-Z]{3}-\d{4}(-(foo|bar))?[^a-z]+";
+        let pattern = Pattern::parse(&format!("/{}/", regex))?;
+        let re = Regex::new(&format!("^{}$", regex))?;
+        for _ in 0..50 {
+            let s = pattern.generate();
+            assert!(re.is_match(&s), "{} doesn't match", s);
+            assert!(s.chars().all(|c| c.is_ascii() && !c.is_ascii_control()));
+        }
+        assert!(Pattern::parse("/[/").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pattern_entropy() -> Result<()> {
+        assert_eq!(Pattern::parse("9999")?.entropy(), 4.0 * 10f64.log2());
+        assert_eq!(Pattern::parse("/(ab|cd)/")?.entropy(), 1.0);
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+    str::FromStr,
+};
+
+use age::{
+    armor::{ArmoredReader, ArmoredWriter, Format},
+    secrecy::ExposeSecret,
+    x25519, Decryptor, Encryptor, IdentityFile,
+};
+use anyhow::{anyhow, Result};
+use chrono::{SecondsFormat, Utc};
+
+const AGE_VERSION_LINE: &[u8] = b"age-encryption.org/";
+const AGE_ARMOR_BEGIN: &[u8] = b"-----BEGIN AGE ENCRYPTED FILE-----";
+
+/// Whether `data` is an age file, binary or ASCII armored.
+pub fn is_age_encrypted(data: &[u8]) -> bool {
+    let data = data.trim_ascii_start();
+    data.starts_with(AGE_VERSION_LINE) || data.starts_with(AGE_ARMOR_BEGIN)
+}
+
+/// A new X25519 identity, written like `age-keygen` does: the identity file
+/// `x25519.key` and the recipient `x25519.pub` to share.
+pub fn x25519_generate() -> Result<HashMap<&'static str, Vec<u8>>> {
+    let identity = x25519::Identity::generate();
+    let recipient = identity.to_public();
+    let created = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    let key = format!(
+        "# created: {}\n# public key: {}\n{}\n",
+        created,
+        recipient,
+        identity.to_string().expose_secret()
+    );
+    let mut map = HashMap::new();
+    map.insert("x25519.key", key.into_bytes());
+    map.insert("x25519.pub", format!("{}\n", recipient).into_bytes());
+    Ok(map)
+}
+
+/// Parse `age1...` recipients, skipping blank lines and `#` comments as in
+/// age recipients files.
+pub fn parse_recipients(recipients: &str) -> Result<Vec<x25519::Recipient>> {
+    recipients
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| {
+            x25519::Recipient::from_str(l).map_err(|e| anyhow!("Invalid recipient {}: {}", l, e))
+        })
+        .collect()
+}
+
+/// Encrypt to X25519 recipients as an ASCII armored age file, which `age -d`
+/// decrypts with any of the matching identities.
+pub fn process_text_encrypt_recipients(
+    reader: &mut dyn Read,
+    recipients: &[x25519::Recipient],
+) -> Result<Vec<u8>> {
+    if recipients.is_empty() {
+        return Err(anyhow!("At least one recipient is required"));
+    }
+    let encryptor = Encryptor::with_recipients(recipients.iter().map(|r| r as _))?;
+    let mut ret = Vec::new();
+    let armor = ArmoredWriter::wrap_output(&mut ret, Format::AsciiArmor)?;
+    let mut writer = encryptor.wrap_output(armor)?;
+    io::copy(reader, &mut writer)?;
+    writer.finish()?.finish()?;
+    Ok(ret)
+}
+
+/// Decrypt a binary or armored age file with the identities in `identity`,
+/// an `age-keygen` style identity file.
+pub fn process_text_decrypt_identity(reader: &mut dyn Read, identity: &[u8]) -> Result<Vec<u8>> {
+    let identities = IdentityFile::from_buffer(identity)?.into_identities()?;
+    if identities.is_empty() {
+        return Err(anyhow!("Identity file has no identities"));
+    }
+    let decryptor = Decryptor::new(ArmoredReader::new(reader))?;
+    let mut plaintext = decryptor.decrypt(identities.iter().map(|i| i.as_ref() as _))?;
+    let mut ret = Vec::new();
+    plaintext.read_to_end(&mut ret)?;
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_text_decrypt_identity() -> Result<()> {
+        // encrypted to x25519.pub by an independent implementation of the age v1 spec
+        let encrypted = include_bytes!("../../fixtures/age/message.txt.age");
+        let identity = include_bytes!("../../fixtures/age/x25519.key");
+        assert!(is_age_encrypted(encrypted));
+        let decrypted = process_text_decrypt_identity(&mut encrypted.as_slice(), identity)?;
+        assert_eq!(decrypted, include_bytes!("../../fixtures/age/message.txt"));
+
+        // both encode the same key in bech32
+        let public = include_str!("../../fixtures/age/x25519.pub").trim();
+        let secret = std::str::from_utf8(identity)?
+            .lines()
+            .last()
+            .unwrap_or_default();
+        let secret = x25519::Identity::from_str(secret).map_err(|e| anyhow!("{}", e))?;
+        assert_eq!(secret.to_public().to_string(), public);
+        assert_eq!(parse_recipients(public)?[0].to_string(), public);
+        assert!(!is_age_encrypted(b"hello"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_text_encrypt_recipients() -> Result<()> {
+        let alice = x25519_generate()?;
+        let bob = x25519_generate()?;
+        let eve = x25519_generate()?;
+        let recipients = [&alice, &bob]
+            .iter()
+            .map(|keys| String::from_utf8_lossy(&keys["x25519.pub"]).into_owned())
+            .collect::<String>();
+        let recipients = parse_recipients(&format!("# team\n{}", recipients))?;
+        assert_eq!(recipients.len(), 2);
+
+        let encrypted = process_text_encrypt_recipients(&mut "hello".as_bytes(), &recipients)?;
+        assert!(is_age_encrypted(&encrypted));
+        assert!(encrypted.starts_with(AGE_ARMOR_BEGIN));
+        for keys in [&alice, &bob] {
+            let decrypted =
+                process_text_decrypt_identity(&mut encrypted.as_slice(), &keys["x25519.key"])?;
+            assert_eq!(decrypted, b"hello");
+        }
+        assert!(
+            process_text_decrypt_identity(&mut encrypted.as_slice(), &eve["x25519.key"]).is_err()
+        );
+
+        assert!(parse_recipients("age1notarecipient").is_err());
+        assert!(process_text_encrypt_recipients(&mut "hello".as_bytes(), &[]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use zxcvbn::zxcvbn;
+
+use crate::SecretFormat;
+
+/// A generated credential with its strength estimate.
+#[derive(Debug, Serialize)]
+pub struct Secret {
+    pub name: String,
+    pub password: String,
+    pub entropy: f64,
+    pub score: u8,
+}
+
+impl Secret {
+    pub fn try_new(name: String, password: String, entropy: f64) -> Result<Self> {
+        let score = zxcvbn(&password, &[])?.score();
+        Ok(Self {
+            name,
+            password,
+            // one decimal is plenty and keeps the output readable
+            entropy: (entropy * 10.0).round() / 10.0,
+            score,
+        })
+    }
+}
+
+/// Expand the `--name` templates into `count` names. `{index}` is replaced by
+/// the 1-based index; a template without it names a single secret. Names left
+/// over once `count` is reached are an error rather than silently dropped.
+pub fn secret_names(templates: &[String], count: usize) -> Result<Vec<String>> {
+    let mut names = Vec::with_capacity(count);
+    let mut templates = templates.iter().peekable();
+    // whether the peeked template has been used for at least one name
+    let mut used = false;
+    while names.len() < count {
+        let index = names.len() + 1;
+        let Some(template) = templates.peek() else {
+            return Err(anyhow!(
+                "{} names given for {} secrets, add more names or an {{index}} template",
+                names.len(),
+                count
+            ));
+        };
+        if template.contains("{index}") {
+            names.push(template.replace("{index}", &index.to_string()));
+            used = true;
+            // a template absorbs the remaining count unless more names follow
+            if templates.len() > count - names.len() {
+                templates.next();
+                used = false;
+            }
+        } else {
+            names.push(template.to_string());
+            templates.next();
+        }
+    }
+    let unused = templates.len() - usize::from(used);
+    if unused > 0 {
+        return Err(anyhow!(
+            "{} names given for {} secrets, remove names or raise --count",
+            names.len() + unused,
+            count
+        ));
+    }
+    Ok(names)
+}
+
+fn env_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Render secrets as plain lines, JSON, YAML or an env file.
+pub fn process_secrets_render(secrets: &[Secret], format: SecretFormat) -> Result<String> {
+    let ret = match format {
+        SecretFormat::Plain => secrets
+            .iter()
+            .map(|s| format!("{}\n", s.password))
+            .collect(),
+        SecretFormat::Json => serde_json::to_string_pretty(secrets)? + "\n",
+        SecretFormat::Yaml => serde_yaml::to_string(secrets)?,
+        SecretFormat::Env => secrets
+            .iter()
+            .map(|s| {
+                format!(
+                    "# entropy: {:.1} bits, strength: {}/4\n{}={}\n",
+                    s.entropy,
+                    s.score,
+                    s.name,
+                    env_quote(&s.password)
+                )
+            })
+            .collect(),
+    };
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_names() -> Result<()> {
+        assert_eq!(
+            secret_names(&["SECRET_{index}".to_string()], 3)?,
+            ["SECRET_1", "SECRET_2", "SECRET_3"]
+        );
+        let names = ["DB_PASSWORD".to_string(), "API_KEY".to_string()];
+        assert_eq!(secret_names(&names, 2)?, ["DB_PASSWORD", "API_KEY"]);
+        assert!(secret_names(&names, 3).is_err());
+        assert!(secret_names(&names, 1).is_err());
+        assert!(secret_names(&["A_{index}".to_string(), "B".to_string()], 1).is_err());
+        let names = ["DB_PASSWORD".to_string(), "TOKEN_{index}".to_string()];
+        assert_eq!(
+            secret_names(&names, 3)?,
+            ["DB_PASSWORD", "TOKEN_2", "TOKEN_3"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_secrets_render() -> Result<()> {
+        let secrets = vec![Secret::try_new(
+            "DB_PASSWORD".to_string(),
+            "it's-$ecret".to_string(),
+            64.0,
+        )?];
+        let env = process_secrets_render(&secrets, SecretFormat::Env)?;
+        assert!(env.ends_with("DB_PASSWORD='it'\\''s-$ecret'\n"));
+        let json = process_secrets_render(&secrets, SecretFormat::Json)?;
+        let value: serde_json::Value = serde_json::from_str(&json)?;
+        assert_eq!(value[0]["name"], "DB_PASSWORD");
+        assert_eq!(value[0]["entropy"], 64.0);
+        assert_eq!(
+            process_secrets_render(&secrets, SecretFormat::Plain)?,
+            "it's-$ecret\n"
+        );
+        Ok(())
+    }
+}
@@ -1,22 +1,55 @@
-use std::{collections::HashMap, io::Read};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
 
-use crate::{process_genpass, TextSignFormat};
-use anyhow::Result;
+use crate::{
+    encode_public_key, encode_signing_key, key_id_hex, load_public_key, load_signing_key,
+    process_genpass, x25519_generate, GenPassConfig, KeyFormat, PublicKeyFile, SigAlgorithm,
+    SignatureFile, TextSignFormat,
+};
+use anyhow::{anyhow, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use blake2::{Blake2b512, Digest};
 use chacha20poly1305::{
-    aead::{Aead, AeadCore, KeyInit, OsRng},
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng, Payload},
     XChaCha20Poly1305,
 };
 use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 
+/// Signers consume their input in chunks, so inputs of any size are signed
+/// in constant memory.
 trait TextSigner {
+    fn algorithm(&self) -> SigAlgorithm;
+
+    fn key_id(&self) -> [u8; 8];
+
     fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>>;
+
+    /// Sign a file, which signers may read memory-mapped.
+    fn sign_file(&self, path: &Path) -> Result<Vec<u8>> {
+        self.sign(&mut File::open(path)?)
+    }
+
+    /// Sign `data` itself rather than its hash, as for trusted comments.
+    fn sign_raw(&self, data: &[u8]) -> Result<Vec<u8>>;
 }
 
 trait TextVerifier {
+    fn key_id(&self) -> [u8; 8];
+
     fn verify(&self, reader: &mut dyn Read, sig: &[u8]) -> Result<bool>;
+
+    fn verify_file(&self, path: &Path, sig: &[u8]) -> Result<bool> {
+        self.verify(&mut File::open(path)?, sig)
+    }
+
+    fn verify_raw(&self, data: &[u8], sig: &[u8]) -> Result<bool>;
 }
 
-struct Blake3 {
+pub(crate) struct Blake3 {
     key: [u8; 32],
 }
 
@@ -26,42 +59,90 @@ struct Ed25519Signer {
 
 struct Ed25519Verifier {
     key: VerifyingKey,
+    key_id: [u8; 8],
 }
 
 impl TextSigner for Blake3 {
+    fn algorithm(&self) -> SigAlgorithm {
+        SigAlgorithm::Blake3
+    }
+
+    fn key_id(&self) -> [u8; 8] {
+        self.blake3_key_id()
+    }
+
     fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>> {
-        let mut buf = Vec::new();
-        reader.read_to_end(&mut buf)?;
-        let ret = blake3::keyed_hash(&self.key, &buf);
+        let ret = self.hasher().update_reader(reader)?.finalize();
         Ok(ret.as_bytes().to_vec())
     }
+
+    fn sign_file(&self, path: &Path) -> Result<Vec<u8>> {
+        let ret = self.hasher().update_mmap_rayon(path)?.finalize();
+        Ok(ret.as_bytes().to_vec())
+    }
+
+    fn sign_raw(&self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(self.keyed_hash(data).as_bytes().to_vec())
+    }
 }
 
 impl TextVerifier for Blake3 {
+    fn key_id(&self) -> [u8; 8] {
+        self.blake3_key_id()
+    }
+
+    // blake3::Hash compares in constant time
     fn verify(&self, reader: &mut dyn Read, sig: &[u8]) -> Result<bool> {
-        let mut buf = Vec::new();
-        reader.read_to_end(&mut buf)?;
-        let ret = blake3::keyed_hash(&self.key, &buf);
-        Ok(ret.as_bytes() == sig)
+        Ok(self.hasher().update_reader(reader)?.finalize() == *sig)
     }
+
+    fn verify_file(&self, path: &Path, sig: &[u8]) -> Result<bool> {
+        Ok(self.hasher().update_mmap_rayon(path)?.finalize() == *sig)
+    }
+
+    fn verify_raw(&self, data: &[u8], sig: &[u8]) -> Result<bool> {
+        Ok(self.keyed_hash(data) == *sig)
+    }
+}
+
+/// BLAKE2b-512 hash of `reader`, which minisign signs instead of the message
+/// so the message can be streamed.
+fn prehash(reader: &mut dyn Read) -> Result<Vec<u8>> {
+    let mut hasher = Blake2b512::new();
+    io::copy(reader, &mut hasher)?;
+    Ok(hasher.finalize().to_vec())
 }
 
 impl TextSigner for Ed25519Signer {
+    fn algorithm(&self) -> SigAlgorithm {
+        SigAlgorithm::Ed25519Hashed
+    }
+
+    fn key_id(&self) -> [u8; 8] {
+        PublicKeyFile::from_raw(self.key.verifying_key().to_bytes()).key_id
+    }
+
     fn sign(&self, reader: &mut dyn Read) -> Result<Vec<u8>> {
-        let mut buf = Vec::new();
-        reader.read_to_end(&mut buf)?;
-        let signature = self.key.sign(&buf);
-        Ok(signature.to_bytes().to_vec())
+        self.sign_raw(&prehash(reader)?)
+    }
+
+    fn sign_raw(&self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(self.key.sign(data).to_bytes().to_vec())
     }
 }
 
 impl TextVerifier for Ed25519Verifier {
+    fn key_id(&self) -> [u8; 8] {
+        self.key_id
+    }
+
     fn verify(&self, reader: &mut dyn Read, sig: &[u8]) -> Result<bool> {
-        let mut buf = Vec::new();
-        reader.read_to_end(&mut buf)?;
-        let sig = (&sig[..64]).try_into()?;
-        let signature = Signature::from_bytes(sig);
-        Ok(self.key.verify(&buf, &signature).is_ok())
+        self.verify_raw(&prehash(reader)?, sig)
+    }
+
+    fn verify_raw(&self, data: &[u8], sig: &[u8]) -> Result<bool> {
+        let signature = Signature::from_slice(sig)?;
+        Ok(self.key.verify(data, &signature).is_ok())
     }
 }
 
@@ -72,13 +153,38 @@ impl Blake3 {
 
     pub fn try_new(key: impl AsRef<[u8]>) -> Result<Self> {
         let key = key.as_ref();
+        if key.len() < 32 {
+            return Err(anyhow::anyhow!("Blake3 key must be at least 32 bytes"));
+        }
         // convert &[u8] to &[u8; 32]
         let key = (&key[..32]).try_into()?;
         Ok(Self::new(key))
     }
 
+    /// Keyed hash of `data`, also used to pseudonymize values consistently.
+    pub fn keyed_hash(&self, data: &[u8]) -> blake3::Hash {
+        blake3::keyed_hash(&self.key, data)
+    }
+
+    fn blake3_key_id(&self) -> [u8; 8] {
+        let hash = self.keyed_hash(b"acli key id");
+        hash.as_bytes()[..8].try_into().expect("hash is 32 bytes")
+    }
+
+    fn hasher(&self) -> blake3::Hasher {
+        blake3::Hasher::new_keyed(&self.key)
+    }
+
+    /// Extendable keyed output, for when more than 32 bytes are needed.
+    pub fn keyed_xof(&self, data: &[u8]) -> blake3::OutputReader {
+        self.hasher().update(data).finalize_xof()
+    }
+
     pub fn generate() -> Result<HashMap<&'static str, Vec<u8>>> {
-        let key = process_genpass(32, true, true, true, true)?;
+        let key = process_genpass(&GenPassConfig {
+            length: 32,
+            ..Default::default()
+        })?;
         let mut map = HashMap::new();
         map.insert("blake3.txt", key.as_bytes().to_vec());
         Ok(map)
@@ -86,47 +192,67 @@ impl Blake3 {
 }
 
 impl Ed25519Signer {
-    pub fn new(key: &[u8; 32]) -> Self {
-        let key = SigningKey::from_bytes(key);
-        Self { key }
-    }
-
+    /// `key` in any format `load_signing_key` detects.
     pub fn try_new(key: impl AsRef<[u8]>) -> Result<Self> {
-        let key = key.as_ref();
-        let key = (&key[..32]).try_into()?;
-        Ok(Self::new(key))
+        Ok(Self {
+            key: load_signing_key(key.as_ref())?,
+        })
     }
 
-    fn generate() -> Result<HashMap<&'static str, Vec<u8>>> {
+    fn generate(
+        key_format: KeyFormat,
+        passphrase: Option<&str>,
+    ) -> Result<HashMap<&'static str, Vec<u8>>> {
         let mut csprng = OsRng;
         let sk: SigningKey = SigningKey::generate(&mut csprng);
-        let pk: VerifyingKey = (&sk).into();
+        let public = PublicKeyFile::from_raw(sk.verifying_key().to_bytes());
         let mut map = HashMap::new();
-        map.insert("ed25519.sk", sk.to_bytes().to_vec());
-        map.insert("ed25519.pk", pk.to_bytes().to_vec());
+        map.insert(
+            "ed25519.sk",
+            encode_signing_key(&sk, key_format, passphrase)?,
+        );
+        map.insert("ed25519.pk", encode_public_key(&public, key_format)?);
+        // the public key for `minisign -V` and `signify -V`
+        map.insert("ed25519.pub", public.to_string().into_bytes());
         Ok(map)
     }
 }
 
 impl Ed25519Verifier {
+    /// `key` in any format `load_public_key` detects.
     pub fn try_new(key: impl AsRef<[u8]>) -> Result<Self> {
-        let key = key.as_ref();
-        let key = (&key[..32]).try_into()?;
-        let key = VerifyingKey::from_bytes(key)?;
-        Ok(Self { key })
+        let public = load_public_key(key.as_ref())?;
+        Ok(Self {
+            key: VerifyingKey::from_bytes(&public.key)?,
+            key_id: public.key_id,
+        })
     }
 }
 
+fn signer(key: &[u8], format: TextSignFormat) -> Result<Box<dyn TextSigner>> {
+    let signer: Box<dyn TextSigner> = match format {
+        TextSignFormat::Blake3 => Box::new(Blake3::try_new(key)?),
+        TextSignFormat::Ed25519 => Box::new(Ed25519Signer::try_new(key)?),
+        TextSignFormat::X25519 => return Err(anyhow!("X25519 keys encrypt, they can't sign")),
+    };
+    Ok(signer)
+}
+
+fn verifier(key: &[u8], format: TextSignFormat) -> Result<Box<dyn TextVerifier>> {
+    let verifier: Box<dyn TextVerifier> = match format {
+        TextSignFormat::Blake3 => Box::new(Blake3::try_new(key)?),
+        TextSignFormat::Ed25519 => Box::new(Ed25519Verifier::try_new(key)?),
+        TextSignFormat::X25519 => return Err(anyhow!("X25519 keys encrypt, they can't sign")),
+    };
+    Ok(verifier)
+}
+
 pub fn process_text_sign(
     reader: &mut dyn Read,
     key: &[u8],
     format: TextSignFormat,
 ) -> Result<Vec<u8>> {
-    let signer: Box<dyn TextSigner> = match format {
-        TextSignFormat::Blake3 => Box::new(Blake3::try_new(key)?),
-        TextSignFormat::Ed25519 => Box::new(Ed25519Signer::try_new(key)?),
-    };
-    signer.sign(reader)
+    signer(key, format)?.sign(reader)
 }
 
 pub fn process_text_verify(
@@ -135,24 +261,148 @@ pub fn process_text_verify(
     sig: &[u8],
     format: TextSignFormat,
 ) -> Result<bool> {
-    let verifier: Box<dyn TextVerifier> = match format {
-        TextSignFormat::Blake3 => Box::new(Blake3::try_new(key)?),
-        TextSignFormat::Ed25519 => Box::new(Ed25519Verifier::try_new(key)?),
+    verifier(key, format)?.verify(reader, sig)
+}
+
+fn sign_input(signer: &dyn TextSigner, input: &str) -> Result<Vec<u8>> {
+    if input == "-" {
+        signer.sign(&mut crate::get_reader(input)?)
+    } else {
+        signer.sign_file(Path::new(input))
+    }
+}
+
+/// Sign `input`, a path or "-" for stdin, into a minisign signature file.
+///
+/// The trusted comment is signed along with the signature and defaults to
+/// minisign's `timestamp:<unix time>\tfile:<name>\thashed`.
+pub fn process_text_sign_detached(
+    input: &str,
+    key: &[u8],
+    format: TextSignFormat,
+    trusted_comment: Option<String>,
+    untrusted_comment: Option<String>,
+) -> Result<SignatureFile> {
+    let signer = signer(key, format)?;
+    let trusted_comment = match trusted_comment {
+        Some(comment) => comment,
+        None => {
+            let name = Path::new(input).file_name().unwrap_or_default();
+            format!(
+                "timestamp:{}\tfile:{}\thashed",
+                crate::now()?,
+                name.to_string_lossy()
+            )
+        }
     };
-    verifier.verify(reader, sig)
+    let untrusted_comment =
+        untrusted_comment.unwrap_or_else(|| "signature from acli secret key".to_string());
+    if trusted_comment.contains('\n') || untrusted_comment.contains('\n') {
+        return Err(anyhow!("Comments must be a single line"));
+    }
+    let signature = sign_input(signer.as_ref(), input)?;
+    let global = signer.sign_raw(&SignatureFile::trusted_data(&signature, &trusted_comment))?;
+    Ok(SignatureFile {
+        untrusted_comment,
+        algorithm: signer.algorithm(),
+        key_id: signer.key_id(),
+        signature,
+        trusted: Some((trusted_comment, global)),
+    })
 }
 
-pub fn process_text_key_generate(format: TextSignFormat) -> Result<HashMap<&'static str, Vec<u8>>> {
-    match format {
-        TextSignFormat::Blake3 => Blake3::generate(),
-        TextSignFormat::Ed25519 => Ed25519Signer::generate(),
+/// Verify `input` against a minisign or signify signature file, picking the
+/// algorithm from the file. Legacy `Ed` signatures cover the message itself
+/// rather than its hash, so the message is read into memory for them.
+pub fn process_text_verify_detached(input: &str, key: &[u8], sig: &SignatureFile) -> Result<bool> {
+    let verifier = verifier(key, sig_format(sig.algorithm))?;
+    if verifier.key_id() != sig.key_id {
+        return Err(anyhow!(
+            "Signature was made with key {}, not {}",
+            key_id_hex(&sig.key_id),
+            key_id_hex(&verifier.key_id())
+        ));
+    }
+    let verified = match sig.algorithm {
+        SigAlgorithm::Ed25519 => {
+            verifier.verify_raw(&crate::get_content(input)?, &sig.signature)?
+        }
+        _ if input == "-" => verifier.verify(&mut crate::get_reader(input)?, &sig.signature)?,
+        _ => verifier.verify_file(Path::new(input), &sig.signature)?,
+    };
+    let trusted = match &sig.trusted {
+        Some((comment, global)) => verifier.verify_raw(
+            &SignatureFile::trusted_data(&sig.signature, comment),
+            global,
+        )?,
+        None => true,
+    };
+    Ok(verified && trusted)
+}
+
+/// Verify a bare signature, as `text sign` printed before signature files: a 32
+/// byte BLAKE3 keyed hash (`B3`) or a 64 byte Ed25519 signature over the message
+/// itself (`Ed`).
+pub fn process_text_verify_raw(input: &str, key: &[u8], sig: &[u8]) -> Result<bool> {
+    let algorithm = match sig.len() {
+        32 => SigAlgorithm::Blake3,
+        64 => SigAlgorithm::Ed25519,
+        n => {
+            return Err(anyhow!(
+                "A bare signature has 32 (blake3) or 64 (ed25519) bytes, not {}",
+                n
+            ))
+        }
+    };
+    let sig = SignatureFile {
+        untrusted_comment: String::new(),
+        algorithm,
+        key_id: verifier(key, sig_format(algorithm))?.key_id(),
+        signature: sig.to_vec(),
+        trusted: None,
+    };
+    process_text_verify_detached(input, key, &sig)
+}
+
+fn sig_format(algorithm: SigAlgorithm) -> TextSignFormat {
+    match algorithm {
+        SigAlgorithm::Blake3 => TextSignFormat::Blake3,
+        SigAlgorithm::Ed25519 | SigAlgorithm::Ed25519Hashed => TextSignFormat::Ed25519,
+    }
+}
+
+/// Generate a key pair, encrypting an Ed25519 secret key with `passphrase` if given.
+pub fn process_text_key_generate(
+    format: TextSignFormat,
+    key_format: KeyFormat,
+    passphrase: Option<&str>,
+) -> Result<HashMap<&'static str, Vec<u8>>> {
+    match (format, key_format, passphrase) {
+        (TextSignFormat::Blake3, _, Some(_)) => {
+            Err(anyhow!("Blake3 keys can't be passphrase protected"))
+        }
+        (TextSignFormat::Blake3, KeyFormat::Raw, None) => Blake3::generate(),
+        (TextSignFormat::Blake3, _, None) => Err(anyhow!("Blake3 keys are always raw")),
+        (TextSignFormat::Ed25519, key_format, passphrase) => {
+            Ed25519Signer::generate(key_format, passphrase)
+        }
+        (TextSignFormat::X25519, KeyFormat::Raw, None) => x25519_generate(),
+        (TextSignFormat::X25519, _, None) => {
+            Err(anyhow!("X25519 keys are always age identity files"))
+        }
+        (TextSignFormat::X25519, _, Some(_)) => {
+            Err(anyhow!("X25519 identities can't be passphrase protected"))
+        }
     }
 }
 
 pub fn process_text_encrypt(reader: &mut dyn Read, key: &[u8]) -> Result<Vec<u8>> {
     let mut buf = Vec::new();
     reader.read_to_end(&mut buf)?;
-    let key = (&key[..32]).try_into()?;
+    if key.len() < 32 {
+        return Err(anyhow::anyhow!("Key must be at least 32 bytes"));
+    }
+    let key = (&key[..32]).into();
     let cipher = XChaCha20Poly1305::new(key);
     let nonce = XChaCha20Poly1305::generate_nonce(&mut rand::rngs::OsRng);
     match cipher.encrypt(&nonce, buf.as_slice().as_ref()) {
@@ -170,17 +420,114 @@ pub fn process_text_encrypt(reader: &mut dyn Read, key: &[u8]) -> Result<Vec<u8>
 pub fn process_text_decrypt(reader: &mut dyn Read, key: &[u8]) -> Result<Vec<u8>> {
     let mut buf = Vec::new();
     reader.read_to_end(&mut buf)?;
-    let key = (&key[..32]).try_into()?;
+    if key.len() < 32 {
+        return Err(anyhow::anyhow!("Key must be at least 32 bytes"));
+    }
+    if buf.len() < 24 {
+        return Err(anyhow::anyhow!("Ciphertext is too short"));
+    }
+    let key = (&key[..32]).into();
     let cipher = XChaCha20Poly1305::new(key);
-    let nonce_and_ciphertext = &buf[..24];
-    let nonce = &nonce_and_ciphertext[..24];
-    let ciphertext = &nonce_and_ciphertext[24..];
+    let (nonce, ciphertext) = buf.split_at(24);
     match cipher.decrypt(nonce.into(), ciphertext) {
         Ok(plaintext) => Ok(plaintext),
         _ => Err(anyhow::anyhow!("Failed to decrypt")),
     }
 }
 
+/// Header of password encrypted data, authenticated as associated data and
+/// followed by the nonce and ciphertext:
+///
+/// ```text
+/// "ACLI-PW1" | m_cost u32 | t_cost u32 | p_cost u32 | salt[16]
+/// ```
+///
+/// The key is Argon2id(password, salt) with the stored parameters, so they
+/// can be raised for new files without breaking old ones.
+const PASSWORD_MAGIC: &[u8; 8] = b"ACLI-PW1";
+const PASSWORD_HEADER_LEN: usize = PASSWORD_MAGIC.len() + 12 + 16;
+/// Argon2id memory cost in KiB (64 MiB), iterations and lanes for new files.
+const PASSWORD_KDF: (u32, u32, u32) = (64 * 1024, 3, 1);
+/// Largest memory cost accepted from a header (1 GiB), so a crafted file
+/// can't exhaust memory.
+const PASSWORD_MAX_M_COST: u32 = 1024 * 1024;
+
+fn password_key(
+    password: &str,
+    salt: &[u8],
+    (m_cost, t_cost, p_cost): (u32, u32, u32),
+) -> Result<[u8; 32]> {
+    if password.is_empty() {
+        return Err(anyhow!("Password must not be empty"));
+    }
+    if m_cost > PASSWORD_MAX_M_COST || t_cost > 64 || p_cost > 16 {
+        return Err(anyhow!("Argon2id parameters in the header are too large"));
+    }
+    let params = Params::new(m_cost, t_cost, p_cost, Some(32)).map_err(|e| anyhow!("{}", e))?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("{}", e))?;
+    Ok(key)
+}
+
+/// Whether `data` was encrypted with `process_text_encrypt_password`.
+pub fn is_password_encrypted(data: &[u8]) -> bool {
+    data.starts_with(PASSWORD_MAGIC)
+}
+
+pub fn process_text_encrypt_password(reader: &mut dyn Read, password: &str) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf)?;
+    let (m_cost, t_cost, p_cost) = PASSWORD_KDF;
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let mut header = PASSWORD_MAGIC.to_vec();
+    for param in [m_cost, t_cost, p_cost] {
+        header.extend_from_slice(&param.to_be_bytes());
+    }
+    header.extend_from_slice(&salt);
+
+    let key = password_key(password, &salt, PASSWORD_KDF)?;
+    let cipher = XChaCha20Poly1305::new(&key.into());
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let payload = Payload {
+        msg: &buf,
+        aad: &header,
+    };
+    let ciphertext = cipher
+        .encrypt(&nonce, payload)
+        .map_err(|_| anyhow!("Failed to encrypt"))?;
+    Ok([header.as_slice(), &nonce, &ciphertext].concat())
+}
+
+pub fn process_text_decrypt_password(reader: &mut dyn Read, password: &str) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf)?;
+    if !is_password_encrypted(&buf) {
+        return Err(anyhow!("Data is not password encrypted"));
+    }
+    if buf.len() < PASSWORD_HEADER_LEN + 24 {
+        return Err(anyhow!("Ciphertext is too short"));
+    }
+    let (header, rest) = buf.split_at(PASSWORD_HEADER_LEN);
+    let param = |i: usize| {
+        let offset = PASSWORD_MAGIC.len() + i * 4;
+        u32::from_be_bytes(header[offset..offset + 4].try_into().expect("4 bytes"))
+    };
+    let salt = &header[PASSWORD_HEADER_LEN - 16..];
+    let key = password_key(password, salt, (param(0), param(1), param(2)))?;
+    let cipher = XChaCha20Poly1305::new(&key.into());
+    let (nonce, ciphertext) = rest.split_at(24);
+    let payload = Payload {
+        msg: ciphertext,
+        aad: header,
+    };
+    cipher
+        .decrypt(nonce.into(), payload)
+        .map_err(|_| anyhow!("Wrong password or corrupted data"))
+}
+
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
@@ -212,4 +559,169 @@ mod tests {
 
         Ok(())
     }
+
+    /// Yields `len` bytes in small reads, without holding them in memory.
+    struct Chunked {
+        len: usize,
+    }
+
+    impl Read for Chunked {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.len).min(1000);
+            for (i, b) in buf[..n].iter_mut().enumerate() {
+                *b = ((self.len - i) % 251) as u8;
+            }
+            self.len -= n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_process_text_sign_streaming() -> Result<()> {
+        let key = b"q8x-yZZMEsQNc8f2uF4iRtvEuW3Dg7Yb";
+        let len = 3 * 1024 * 1024 + 17;
+        let mut data = Vec::new();
+        Chunked { len }.read_to_end(&mut data)?;
+
+        let sig = process_text_sign(&mut Chunked { len }, key, TextSignFormat::Blake3)?;
+        assert_eq!(sig, blake3::keyed_hash(key, &data).as_bytes());
+
+        let file = std::env::temp_dir().join(format!("acli-sign-{}", std::process::id()));
+        std::fs::write(&file, &data)?;
+        let blake3 = Blake3::try_new(key)?;
+        let file_sig = blake3.sign_file(&file);
+        let verified = blake3.verify_file(&file, &sig);
+        std::fs::remove_file(&file)?;
+        assert_eq!(file_sig?, sig);
+        assert!(verified?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_text_sign_ed25519() -> Result<()> {
+        let keys = process_text_key_generate(TextSignFormat::Ed25519, KeyFormat::Raw, None)?;
+        let (sk, pk) = (&keys["ed25519.sk"], &keys["ed25519.pk"]);
+        let len = 2 * 1024 * 1024;
+        let sig = process_text_sign(&mut Chunked { len }, sk, TextSignFormat::Ed25519)?;
+        let verify = |len, sig: &[u8]| {
+            process_text_verify(&mut Chunked { len }, pk, sig, TextSignFormat::Ed25519)
+        };
+        assert!(verify(len, &sig)?);
+        assert!(!verify(len - 1, &sig)?);
+        assert!(verify(len, &sig[..10]).is_err());
+        assert!(verify(len, &keys["ed25519.pub"]).is_err());
+
+        // Ed25519 over the BLAKE2b-512 hash of the message, as minisign signs
+        let mut data = Vec::new();
+        Chunked { len }.read_to_end(&mut data)?;
+        let key = SigningKey::from_bytes(sk[..32].try_into()?);
+        let expected = key.sign(&Blake2b512::digest(&data));
+        assert_eq!(sig, expected.to_bytes());
+        Ok(())
+    }
+
+    const MINISIGN: &str = "fixtures/minisign";
+
+    #[test]
+    fn test_process_text_sign_detached() -> Result<()> {
+        let input = format!("{}/message.txt", MINISIGN);
+        let sk = std::fs::read(format!("{}/ed25519.sk", MINISIGN))?;
+        let comment = "timestamp:1700000000\tfile:message.txt\thashed".to_string();
+        let sig =
+            process_text_sign_detached(&input, &sk, TextSignFormat::Ed25519, Some(comment), None)?;
+        assert_eq!(
+            sig.to_string(),
+            std::fs::read_to_string(format!("{}.sig", input))?
+        );
+
+        let sig = process_text_sign_detached(&input, &sk, TextSignFormat::Ed25519, None, None)?;
+        let (comment, _) = sig.trusted.as_ref().expect("trusted comment");
+        assert!(comment.starts_with("timestamp:"));
+        assert!(comment.ends_with("\tfile:message.txt\thashed"));
+
+        let key = b"q8x-yZZMEsQNc8f2uF4iRtvEuW3Dg7Yb";
+        let sig = process_text_sign_detached(&input, key, TextSignFormat::Blake3, None, None)?;
+        let sig: SignatureFile = sig.to_string().parse()?;
+        assert_eq!(sig.algorithm, SigAlgorithm::Blake3);
+        assert!(process_text_verify_detached(&input, key, &sig)?);
+        let other = b"A8x-yZZMEsQNc8f2uF4iRtvEuW3Dg7Yb";
+        assert!(process_text_verify_detached(&input, other, &sig).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_text_verify_detached() -> Result<()> {
+        let input = format!("{}/message.txt", MINISIGN);
+        let read_sig = |name: &str| -> Result<SignatureFile> {
+            std::fs::read_to_string(format!("{}/{}", MINISIGN, name))?.parse()
+        };
+        for key in ["minisign.pub", "ed25519.pk"] {
+            let key = std::fs::read(format!("{}/{}", MINISIGN, key))?;
+            for sig in ["message.txt.sig", "message.txt.signify.sig"] {
+                assert!(process_text_verify_detached(&input, &key, &read_sig(sig)?)?);
+            }
+            let other = format!("{}/ed25519.pk", MINISIGN);
+            assert!(!process_text_verify_detached(
+                &other,
+                &key,
+                &read_sig("message.txt.sig")?
+            )?);
+
+            // a tampered trusted comment fails verification
+            let mut sig = read_sig("message.txt.sig")?;
+            if let Some((comment, _)) = sig.trusted.as_mut() {
+                comment.push_str(" tampered");
+            }
+            assert!(!process_text_verify_detached(&input, &key, &sig)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_text_verify_raw() -> Result<()> {
+        let input = format!("{}/message.txt", MINISIGN);
+        let pk = std::fs::read(format!("{}/ed25519.pk", MINISIGN))?;
+        // pure Ed25519 over the message, made with Python's cryptography package
+        let sig = "5SCD6PIthsK556XGYcQVbDq57qXwWKTLU1feNG8KfEPmeZtEXcs7CVu70IXYnbB_ESJZVEiQTEt6JaOC1VlgBQ";
+        let sig = URL_SAFE_NO_PAD.decode(sig)?;
+        assert!(process_text_verify_raw(&input, &pk, &sig)?);
+        let other = format!("{}/ed25519.pk", MINISIGN);
+        assert!(!process_text_verify_raw(&other, &pk, &sig)?);
+
+        let key = b"q8x-yZZMEsQNc8f2uF4iRtvEuW3Dg7Yb";
+        let sig = blake3::keyed_hash(key, &std::fs::read(&input)?);
+        assert!(process_text_verify_raw(&input, key, sig.as_bytes())?);
+        assert!(process_text_verify_raw(&input, key, &sig.as_bytes()[..16]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_text_encrypt_decrypt() -> Result<()> {
+        let key = b"q8x-yZZMEsQNc8f2uF4iRtvEuW3Dg7Yb";
+        let encrypted = process_text_encrypt(&mut "hello".as_bytes(), key)?;
+        let decrypted = process_text_decrypt(&mut encrypted.as_slice(), key)?;
+        assert_eq!(decrypted, b"hello");
+        assert!(process_text_decrypt(&mut &encrypted[..10], key).is_err());
+        assert!(!is_password_encrypted(&encrypted));
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_text_encrypt_password() -> Result<()> {
+        let encrypted = process_text_encrypt_password(&mut "hello".as_bytes(), "hunter2")?;
+        assert!(is_password_encrypted(&encrypted));
+        let decrypted = process_text_decrypt_password(&mut encrypted.as_slice(), "hunter2")?;
+        assert_eq!(decrypted, b"hello");
+        assert!(process_text_decrypt_password(&mut encrypted.as_slice(), "hunter3").is_err());
+
+        // the header is authenticated, lowering the KDF cost breaks decryption
+        let mut tampered = encrypted.clone();
+        tampered[PASSWORD_MAGIC.len() + 7] = 2;
+        assert!(process_text_decrypt_password(&mut tampered.as_slice(), "hunter2").is_err());
+        let mut huge = encrypted.clone();
+        huge[PASSWORD_MAGIC.len()] = 0xff;
+        assert!(process_text_decrypt_password(&mut huge.as_slice(), "hunter2").is_err());
+        assert!(process_text_encrypt_password(&mut "hello".as_bytes(), "").is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,186 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use calamine::{open_workbook_auto, Data, DataType, Reader};
+use rust_xlsxwriter::{Format, Workbook};
+use serde_json::{Map, Value};
+
+const SPREADSHEET_EXTENSIONS: &[&str] = &["xlsx", "xlsm", "xlsb", "xls", "ods"];
+
+/// Whether `path` should be read as a spreadsheet rather than CSV.
+pub(crate) fn is_spreadsheet(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| SPREADSHEET_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+fn to_value(cell: &Data) -> Value {
+    match cell {
+        Data::Empty => Value::Null,
+        Data::Bool(b) => Value::from(*b),
+        Data::Int(i) => Value::from(*i),
+        // spreadsheets store every number as a float, keep whole numbers as integers
+        Data::Float(f) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => Value::from(*f as i64),
+        Data::Float(f) => Value::from(*f),
+        Data::DateTime(_) => match cell.as_datetime() {
+            Some(dt) if dt.time() == chrono::NaiveTime::MIN => {
+                Value::from(dt.date().format("%Y-%m-%d").to_string())
+            }
+            Some(dt) => Value::from(dt.format("%Y-%m-%dT%H:%M:%S").to_string()),
+            None => Value::from(cell.to_string()),
+        },
+        Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => Value::from(s.clone()),
+        Data::Error(e) => Value::from(e.to_string()),
+    }
+}
+
+/// Read a worksheet (the first one unless `sheet` is given), using its first row as header.
+pub(crate) fn read_sheet(path: &str, sheet: Option<&str>) -> Result<Vec<Map<String, Value>>> {
+    let mut workbook = open_workbook_auto(path)?;
+    let name = match sheet {
+        Some(name) => name.to_string(),
+        None => workbook
+            .sheet_names()
+            .first()
+            .cloned()
+            .ok_or_else(|| anyhow!("{} has no worksheets", path))?,
+    };
+    let range = workbook
+        .worksheet_range(&name)
+        .map_err(|e| anyhow!("Failed to read sheet {}: {}", name, e))?;
+    let mut rows = range.rows();
+    let headers = rows
+        .next()
+        .map(|row| row.iter().map(|c| c.to_string()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    Ok(rows
+        .filter(|row| row.iter().any(|c| !c.is_empty()))
+        .map(|row| {
+            headers
+                .iter()
+                .zip(row.iter())
+                .map(|(h, c)| (h.clone(), to_value(c)))
+                .collect()
+        })
+        .collect())
+}
+
+/// Largest magnitude up to which every integer is exact in an f64 (2^53).
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+/// Parse CSV-sourced strings into numbers so that spreadsheet cells are typed,
+/// leaving values like zip codes with leading zeros untouched. Excel keeps only
+/// 15 significant digits, so longer values (IDs, card numbers) stay text too.
+fn as_number(s: &str) -> Option<f64> {
+    let digits = s.trim_start_matches('-');
+    if digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.") {
+        return None;
+    }
+    if s.bytes().filter(u8::is_ascii_digit).count() > 15 {
+        return None;
+    }
+    s.parse::<f64>()
+        .ok()
+        .filter(|f| f.is_finite() && f.abs() <= MAX_SAFE_INTEGER)
+}
+
+/// Write `records` as a worksheet with a bold header row and fitted column widths.
+pub(crate) fn write_xlsx(records: &[Value], output: &str) -> Result<()> {
+    let mut headers: Vec<&String> = Vec::new();
+    for record in records {
+        if let Value::Object(map) = record {
+            for k in map.keys() {
+                if !headers.contains(&k) {
+                    headers.push(k);
+                }
+            }
+        }
+    }
+
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet();
+    let bold = Format::new().set_bold();
+    for (col, h) in headers.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, h.as_str(), &bold)?;
+    }
+    for (row, record) in records.iter().enumerate() {
+        let row = row as u32 + 1;
+        for (col, h) in headers.iter().enumerate() {
+            let col = col as u16;
+            match record.get(h.as_str()) {
+                None | Some(Value::Null) => {}
+                Some(Value::Bool(b)) => {
+                    sheet.write_boolean(row, col, *b)?;
+                }
+                Some(Value::Number(n)) => match as_number(&n.to_string()) {
+                    Some(n) => {
+                        sheet.write_number(row, col, n)?;
+                    }
+                    None => {
+                        sheet.write_string(row, col, n.to_string())?;
+                    }
+                },
+                Some(Value::String(s)) => match as_number(s) {
+                    Some(n) => {
+                        sheet.write_number(row, col, n)?;
+                    }
+                    None => {
+                        sheet.write_string(row, col, s)?;
+                    }
+                },
+                Some(v) => {
+                    sheet.write_string(row, col, v.to_string())?;
+                }
+            }
+        }
+    }
+    sheet.autofit();
+    workbook.save(output)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_xlsx_round_trip() -> Result<()> {
+        let output = std::env::temp_dir().join(format!("acli-{}.xlsx", std::process::id()));
+        let output = output.display().to_string();
+        let records = vec![
+            json!({ "Name": "布冯", "Kit Number": "1", "Zip": "00123", "Captain": true }),
+            json!({ "Name": "Mattia Perin", "Kit Number": 37, "Zip": null }),
+            json!({ "Name": "Id", "Kit Number": "1234567890123456789", "Zip": 1234567890123456789u64 }),
+        ];
+        write_xlsx(&records, &output)?;
+        assert!(is_spreadsheet(&output));
+
+        let rows = read_sheet(&output, None)?;
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0]["Name"], json!("布冯"));
+        assert_eq!(rows[0]["Kit Number"], json!(1));
+        assert_eq!(rows[0]["Zip"], json!("00123"));
+        assert_eq!(rows[0]["Captain"], json!(true));
+        assert_eq!(rows[1]["Kit Number"], json!(37));
+        assert_eq!(rows[1]["Captain"], Value::Null);
+        // 19 digits would be rounded as a number, keep them as text
+        assert_eq!(rows[2]["Kit Number"], json!("1234567890123456789"));
+        assert_eq!(rows[2]["Zip"], json!("1234567890123456789"));
+        assert!(read_sheet(&output, Some("missing")).is_err());
+        std::fs::remove_file(output)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_as_number() {
+        assert_eq!(as_number("37"), Some(37.0));
+        assert_eq!(as_number("-1.5"), Some(-1.5));
+        assert_eq!(as_number("00123"), None);
+        assert_eq!(as_number("123456789012345"), Some(123456789012345.0));
+        assert_eq!(as_number("1234567890123456"), None);
+        assert_eq!(as_number("1e20"), None);
+        assert_eq!(as_number("abc"), None);
+    }
+}
@@ -1,6 +1,11 @@
-use std::{fs::File, io::Read};
+use std::{
+    fs::File,
+    io::{Read, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use anyhow::Result;
+use rand::rngs::ThreadRng;
 
 pub fn get_reader(input: &str) -> Result<Box<dyn Read>> {
     let reader: Box<dyn Read> = if input == "-" {
@@ -18,3 +23,22 @@ pub fn get_content(input: &str) -> Result<Vec<u8>> {
     Ok(buf)
 }
 
+pub fn get_writer(output: &str) -> Result<Box<dyn Write>> {
+    let writer: Box<dyn Write> = if output == "-" {
+        Box::new(std::io::stdout())
+    } else {
+        Box::new(File::create(output)?)
+    };
+    Ok(writer)
+}
+
+/// The RNG behind generated passwords and identifiers: a CSPRNG seeded from
+/// the OS and reseeded periodically, cheap enough to call per value.
+pub fn secure_rng() -> ThreadRng {
+    rand::thread_rng()
+}
+
+/// Seconds since the Unix epoch.
+pub fn now() -> Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}